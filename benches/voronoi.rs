@@ -0,0 +1,35 @@
+// Frame time of the voronoi sketch against site count, with and without a
+// repulsion cutoff. No cutoff is the same work as the original all-pairs
+// update.
+//
+// cargo bench --bench voronoi
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use duststorm::voronoi::{Settings, Sim};
+use nannou::geom::Rect;
+
+fn update(c: &mut Criterion) {
+    let win = Rect::from_w_h(1000.0, 1000.0);
+    let mut group = c.benchmark_group("voronoi_update");
+    for sites in [250, 500, 1000, 2000, 4000] {
+        for cutoff in [0.0, 250.0, 100.0] {
+            let settings = Settings {
+                agent_count: sites,
+                repulsion_cutoff: cutoff,
+                ..Settings::default()
+            };
+            let mut sim = Sim::new(win, 1234, settings);
+            let name = match cutoff > 0.0 {
+                true => format!("cutoff_{}", cutoff),
+                false => "no_cutoff".to_string(),
+            };
+            group.bench_with_input(BenchmarkId::new(name, sites), &sites, |b, _| {
+                b.iter(|| sim.update())
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, update);
+criterion_main!(benches);
@@ -0,0 +1,335 @@
+// An accumulation buffer, for long-exposure renders: agents deposit light into
+// a float image, which decays exponentially (and optionally diffuses) over
+// time, and is tone mapped down to displayable colours only when it's shown.
+// Unlike fading with translucent black, nothing is left behind once it's
+// decayed, and dense areas build up smoothly instead of clipping.
+//
+// Light is added up in linear RGB, in pixels with the same layout as `Canvas`:
+// origin at the centre, y up.
+
+use std::path::Path;
+
+use nannou::geom::Rect;
+use palette::{LinSrgb, Srgb};
+use serde::{Deserialize, Serialize};
+
+use crate::color::Color;
+use crate::pos::Pos;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToneMap {
+    // straight through, clipping anything past white
+    Linear,
+    // ln(1 + x), which keeps detail in dense areas without burning out
+    Log,
+    // a filmic curve (the ACES fit), with a soft toe and shoulder
+    Filmic,
+}
+
+impl ToneMap {
+    // the next tone mapping in the T-key cycle
+    pub fn next(self) -> ToneMap {
+        match self {
+            ToneMap::Linear => ToneMap::Log,
+            ToneMap::Log => ToneMap::Filmic,
+            ToneMap::Filmic => ToneMap::Linear,
+        }
+    }
+
+    // light `x` (0 or more) mapped into 0..1 for display
+    fn apply(self, x: f32) -> f32 {
+        let mapped = match self {
+            ToneMap::Linear => x,
+            ToneMap::Log => x.ln_1p(),
+            ToneMap::Filmic => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+        };
+        mapped.clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    // off to paint straight onto the window instead
+    pub enabled: bool,
+    // light each agent adds per step
+    pub deposit: f32,
+    // the fraction of light lost per second is 1 - e^-decay; 0 keeps
+    // everything, for a true long exposure
+    pub decay: f32,
+    // the standard deviation, in pixels, of the Gaussian blur spreading the
+    // light out each step; 0 for none
+    pub blur: f32,
+    pub tone_map: ToneMap,
+    // multiplies the light before tone mapping
+    pub exposure: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            enabled: false,
+            deposit: 0.1,
+            decay: 0.5,
+            blur: 0.0,
+            tone_map: ToneMap::Filmic,
+            exposure: 1.0,
+        }
+    }
+}
+
+pub struct Accumulator {
+    width: u32,
+    height: u32,
+    // row-major, top row first, linear RGB
+    pixels: Vec<[f32; 3]>,
+}
+
+impl Accumulator {
+    // a dark buffer covering the window `win`
+    pub fn new(win: Rect) -> Self {
+        let (width, height) = (win.w().max(1.0) as u32, win.h().max(1.0) as u32);
+        Accumulator {
+            width,
+            height,
+            pixels: vec![[0.0; 3]; (width * height) as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.iter_mut().for_each(|p| *p = [0.0; 3]);
+    }
+
+    // lose light at `rate` per second (as in `Settings::decay`) over `dt`
+    // seconds
+    pub fn decay(&mut self, rate: f32, dt: f32) {
+        let keep = (-rate * dt).exp();
+        if keep < 1.0 {
+            self.pixels
+                .iter_mut()
+                .for_each(|p| p.iter_mut().for_each(|c| *c *= keep));
+        }
+    }
+
+    // add `amount` of `color` spread evenly along the segment from `start` to
+    // `end`, so that an agent leaves the same light per step however far it
+    // moves, and more where it lingers
+    pub fn deposit_line(&mut self, start: Pos, end: Pos, color: Color, amount: f32) {
+        let linear: LinSrgb = Srgb::new(color.red, color.green, color.blue).into_linear();
+        let light = [linear.red, linear.green, linear.blue].map(|c| c * amount * color.alpha);
+        let (a, b) = (self.to_pixel(start), self.to_pixel(end));
+        // about one splat per pixel along the segment
+        let splats = ((b - a).magnitude().ceil() as usize).max(1);
+        let each = light.map(|c| c / splats as f32);
+        for i in 0..splats {
+            let t = (i as f32 + 0.5) / splats as f32;
+            self.splat(a + (b - a) * t, each);
+        }
+    }
+
+    // spread light over the four pixels nearest `p`, by how near each is
+    fn splat(&mut self, p: Pos, light: [f32; 3]) {
+        let (x, y) = (p.x - 0.5, p.y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        for (dx, dy, weight) in [
+            (0, 0, (1.0 - fx) * (1.0 - fy)),
+            (1, 0, fx * (1.0 - fy)),
+            (0, 1, (1.0 - fx) * fy),
+            (1, 1, fx * fy),
+        ] {
+            let (px, py) = (x0 as i64 + dx, y0 as i64 + dy);
+            if px < 0 || py < 0 || px >= self.width as i64 || py >= self.height as i64 {
+                continue;
+            }
+            let pixel = &mut self.pixels[(py * self.width as i64 + px) as usize];
+            for (c, l) in pixel.iter_mut().zip(light) {
+                *c += l * weight;
+            }
+        }
+    }
+
+    // a separable Gaussian blur with standard deviation `sigma` pixels,
+    // treating everything past the edges as dark
+    pub fn blur(&mut self, sigma: f32) {
+        if sigma <= 0.0 {
+            return;
+        }
+        let radius = (sigma * 3.0).ceil() as i64;
+        let kernel: Vec<f32> = (-radius..=radius)
+            .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+            .collect();
+        let total: f32 = kernel.iter().sum();
+        let kernel: Vec<f32> = kernel.iter().map(|k| k / total).collect();
+        let (w, h) = (self.width as usize, self.height as usize);
+        let mut scratch = vec![[0.0; 3]; self.pixels.len()];
+        // along the rows into the scratch buffer, then down the columns back
+        convolve(&self.pixels, &mut scratch, &kernel, (w, 1), (h, w));
+        convolve(&scratch, &mut self.pixels, &kernel, (h, w), (w, 1));
+    }
+
+    // the buffer tone mapped to 8-bit sRGB, as opaque RGBA
+    pub fn to_rgba8(&self, exposure: f32, tone_map: ToneMap) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|p| {
+                let [r, g, b] = p.map(|c| tone_map.apply(c * exposure));
+                let srgb: Srgb<u8> = Srgb::from_linear(LinSrgb::new(r, g, b));
+                [srgb.red, srgb.green, srgb.blue, 255]
+            })
+            .collect()
+    }
+
+    pub fn save_png<P: AsRef<Path>>(
+        &self,
+        path: P,
+        exposure: f32,
+        tone_map: ToneMap,
+    ) -> image::ImageResult<()> {
+        image::save_buffer(
+            path,
+            &self.to_rgba8(exposure, tone_map),
+            self.width,
+            self.height,
+            image::ColorType::Rgba8,
+        )
+    }
+
+    // window coordinates to (fractional) pixel coordinates
+    fn to_pixel(&self, pos: Pos) -> Pos {
+        Pos::new(
+            pos.x + self.width as f32 / 2.0,
+            self.height as f32 / 2.0 - pos.y,
+        )
+    }
+}
+
+// Convolve lines of pixels with a kernel centred on each, treating everything
+// past the ends as dark. There are `lines` lines `line_stride` apart, each
+// `len` pixels `stride` apart.
+fn convolve(
+    src: &[[f32; 3]],
+    dst: &mut [[f32; 3]],
+    kernel: &[f32],
+    (len, stride): (usize, usize),
+    (lines, line_stride): (usize, usize),
+) {
+    let radius = kernel.len() / 2;
+    for line in 0..lines {
+        let base = line * line_stride;
+        for i in 0..len {
+            let mut sum = [0.0; 3];
+            let first = i.saturating_sub(radius);
+            let last = (i + radius).min(len - 1);
+            for at in first..=last {
+                let weight = kernel[at + radius - i];
+                let p = src[base + at * stride];
+                sum.iter_mut().zip(p).for_each(|(s, c)| *s += c * weight);
+            }
+            dst[base + i * stride] = sum;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::WHITE;
+
+    fn total(accumulator: &Accumulator) -> [f32; 3] {
+        accumulator.pixels.iter().fold([0.0; 3], |mut sum, p| {
+            sum.iter_mut().zip(p).for_each(|(s, c)| *s += c);
+            sum
+        })
+    }
+
+    fn lit() -> Accumulator {
+        let mut accumulator = Accumulator::new(Rect::from_w_h(64.0, 48.0));
+        accumulator.deposit_line(Pos::new(-10.0, -5.0), Pos::new(12.0, 7.0), WHITE, 1.0);
+        accumulator
+    }
+
+    #[test]
+    fn decay_per_second_whatever_the_step() {
+        let rate = 0.7;
+        let before = total(&lit())[0];
+        for steps in [1, 7, 60, 240] {
+            let mut accumulator = lit();
+            for _ in 0..steps {
+                accumulator.decay(rate, 1.0 / steps as f32);
+            }
+            let lost = 1.0 - total(&accumulator)[0] / before;
+            assert!(
+                (lost - (1.0 - (-rate).exp())).abs() < 1e-4,
+                "{} steps",
+                steps
+            );
+        }
+        // no decay keeps everything
+        let mut accumulator = lit();
+        accumulator.decay(0.0, 1.0);
+        assert_eq!(total(&accumulator), total(&lit()));
+    }
+
+    #[test]
+    fn lines_deposit_the_same_light_however_long() {
+        let color = Color::new(1.0, 0.5, 0.25, 0.5);
+        let mut expected = None;
+        for (start, end) in [
+            (Pos::new(0.0, 0.0), Pos::new(0.0, 0.0)),
+            (Pos::new(-3.2, 1.1), Pos::new(-2.0, 1.5)),
+            (Pos::new(-20.0, -15.0), Pos::new(25.0, 18.0)),
+        ] {
+            let mut accumulator = Accumulator::new(Rect::from_w_h(64.0, 48.0));
+            accumulator.deposit_line(start, end, color, 2.0);
+            let light = total(&accumulator);
+            let expected = *expected.get_or_insert(light);
+            for (a, b) in light.iter().zip(expected) {
+                assert!((a - b).abs() < 1e-4);
+            }
+        }
+        // the amount, of the colour in linear light, scaled by its alpha
+        let red = expected.unwrap()[0];
+        assert!((red - 2.0 * 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn blur_keeps_the_light_it_spreads() {
+        for sigma in [0.5, 1.0, 2.5] {
+            let mut accumulator = lit();
+            let before = total(&accumulator);
+            let peak = accumulator.pixels.iter().map(|p| p[0]).fold(0.0, f32::max);
+            accumulator.blur(sigma);
+            let after = total(&accumulator);
+            for (a, b) in after.iter().zip(before) {
+                assert!((a - b).abs() < 1e-3 * b, "sigma {}", sigma);
+            }
+            let blurred_peak = accumulator.pixels.iter().map(|p| p[0]).fold(0.0, f32::max);
+            assert!(blurred_peak < peak);
+        }
+    }
+
+    #[test]
+    fn tone_maps_stay_displayable() {
+        for tone_map in [ToneMap::Linear, ToneMap::Log, ToneMap::Filmic] {
+            assert_eq!(tone_map.apply(0.0), 0.0, "{:?}", tone_map);
+            let mut last = 0.0;
+            for i in 0..=400 {
+                let x = (i as f32 / 20.0).exp() - 1.0;
+                let y = tone_map.apply(x);
+                assert!((0.0..=1.0).contains(&y), "{:?}({}) = {}", tone_map, x, y);
+                assert!(y >= last, "{:?} isn't increasing at {}", tone_map, x);
+                last = y;
+            }
+        }
+    }
+}
@@ -1,86 +1,260 @@
-use crate::pos;
-use nannou::noise::{NoiseFn, Perlin};
-use nannou::prelude::*;
-use pos::Pos;
+use nannou::geom::Rect;
+use rand::Rng;
+
+use crate::field::FlowField;
+use crate::motion::{self, REFERENCE_FPS};
+use crate::pos::Pos;
+use crate::rng::{self, SimRng};
+
+// how many spots `respawn_weighted` tries before settling for the last
+const RESPAWN_TRIES: usize = 16;
+// the most seconds an agent starts out with to live
+pub const MAX_TTL: f32 = 10.0;
 
 #[derive(Clone)]
 pub struct Agent {
     pub pos: Pos, // (x,y) position
     pub prev_pos: Pos,
-    step_size: f32, // in pixels
-    pub ttl: f32,   // how many seconds to survive before regenerating in random location
+    // the step before its last, for how its path curves
+    pub prev_step: Pos,
+    pub step_size: f32, // in pixels
+    pub ttl: f32,       // how many seconds to survive before regenerating in random location
     pub z_offset: f32,
+    // seconds since it spawned
+    pub age: f32,
+    // in pixels per second; only used with inertia
+    pub vel: Pos,
+    pub motion: motion::Settings,
+    // which of the sim's targets it chases
+    pub target: usize,
+    // whether it's been respawned, rather than being one of the first
+    pub respawned: bool,
     z: f32,
+    rng: SimRng,
 }
 
 impl Agent {
-    pub fn new(win: Rect, start_origin: bool, step_size: f32) -> Agent {
+    pub fn new(
+        win: Rect,
+        start_origin: bool,
+        step_size: f32,
+        motion: motion::Settings,
+        rng: &mut SimRng,
+    ) -> Agent {
         let pos = match start_origin {
             true => Pos::new(0.0, 0.0),
             false => Pos::new(
-                random_range(win.left() * 1.1, win.right() * 1.1),
-                random_range(win.bottom() * 1.1, win.top() * 1.1),
+                rng.gen_range(win.left() * 1.1..win.right() * 1.1),
+                rng.gen_range(win.bottom() * 1.1..win.top() * 1.1),
             ),
         };
         Agent {
-            pos: pos,
+            pos,
             prev_pos: pos,
-            step_size: step_size,
-            ttl: random_range(2.0, 10.0),
-            z_offset: random_range(0f32, 1.0f32),
+            prev_step: Pos::new(0.0, 0.0),
+            step_size,
+            ttl: rng.gen_range(2.0..MAX_TTL),
+            z_offset: rng.gen_range(0f32..1.0f32),
+            age: 0.0,
+            vel: Pos::new(0.0, 0.0),
+            motion,
+            target: 0,
+            respawned: false,
             z: 0.0,
+            rng: rng::split(rng),
         }
     }
 
-    pub fn update1(&mut self, noise: Perlin, target: Pos, noise_scale: f64) {
-        // take a fixed step in the noise direction
-        let angle = noise.get([
+    // `target_accel` is the fraction of the distance to the target covered
+    // each step, `steer` is any extra step to take, like flocking, and `dt` is
+    // the seconds since the last update
+    pub fn update1(
+        &mut self,
+        field: &dyn FlowField,
+        target: Pos,
+        noise_scale: f64,
+        target_accel: f32,
+        steer: Pos,
+        dt: f32,
+    ) {
+        self.prev_step = self.last_step();
+        self.prev_pos = self.pos;
+        let dir = field.direction([
             self.pos.x as f64 / noise_scale,
             self.pos.y as f64 / noise_scale,
-            (self.z + self.z_offset * 4.0) as f64,
-        ]) as f32;
-        let angle = angle * 2.0 * PI;
-        self.pos.x += angle.cos() * self.step_size;
-        self.pos.y += angle.sin() * self.step_size;
-        // then take a proportional step in the target direction
-        let dxy = target - self.pos;
-        let dxy = dxy * 0.03; // acceleration factor, tweak for best results
-        self.pos.x += dxy.x;
-        self.pos.y += dxy.y;
+            (self.z_offset * 4.0) as f64,
+            self.z as f64,
+        ]);
+        self.step(dir, target, target_accel, steer, dt);
+        self.age += dt;
 
         // lastly - push z offset a bit so we're constantly sliding up the x axis of the noise space
-        self.z += 0.02;
+        self.z += match self.motion.inertia {
+            true => 0.02 * REFERENCE_FPS * dt,
+            false => 0.02,
+        };
+    }
+
+    // how far the agent moved in its last step
+    pub fn last_step(&self) -> Pos {
+        self.pos - self.prev_pos
+    }
+
+    // whether the agent has outlived its ttl and should be respawned
+    pub fn expired(&self) -> bool {
+        self.ttl < 0.0
+    }
+
+    // replace the agent with a fresh one somewhere random in the window,
+    // continuing its random stream
+    pub fn respawn(&mut self, win: Rect, step_size: f32) {
+        *self = Agent::new(win, false, step_size, self.motion, &mut self.rng);
+        self.respawned = true;
+    }
+
+    // like `respawn`, but trying again (a few times at most) until a spot
+    // passes `chance`, the probability of keeping it, so agents respawn more
+    // often where that's high
+    pub fn respawn_weighted(&mut self, win: Rect, step_size: f32, chance: impl Fn(Pos) -> f32) {
+        for _ in 0..RESPAWN_TRIES {
+            self.respawn(win, step_size);
+            if self.rng.gen::<f32>() < chance(self.pos) {
+                return;
+            }
+        }
     }
 
+    // `pace` scales the step taken through the noise, from 0 (standing
+    // still) to 1 (a full `step_size`)
+    #[allow(clippy::too_many_arguments)]
     pub fn update2(
         &mut self,
-        noise: Perlin,
+        field: &dyn FlowField,
         noise_scale: f64,
         target: Pos,
-        win: Rect,
-        time: nannou::state::Time,
+        target_accel: f32,
+        pace: f32,
+        time: f32,
+        dt: f32,
     ) {
-        if self.ttl < 0.0 {
-            *self = Agent::new(win, false, 2f32);
-            self.prev_pos = self.pos;
-        } else {
-            self.prev_pos = self.pos;
-            // take a fixed step in the noise direction
-            let angle = noise.get([
-                self.pos.x as f64 / noise_scale,
-                self.pos.y as f64 / noise_scale,
-                time.since_start.as_secs_f64() / 25.0,
-            ]) as f32;
-            let angle = angle * 2.0 * PI;
-            self.pos.x += angle.cos() * self.step_size;
-            self.pos.y += angle.sin() * self.step_size;
-            // then take a proportional step in the target direction
-            let dxy = target - self.pos;
-            let dxy = dxy * 0.002; // acceleration factor, tweak for best results
-            self.pos.x += dxy.x;
-            self.pos.y += dxy.y;
-            // update ttl
-            self.ttl -= time.since_prev_update.as_secs_f32();
+        self.prev_step = self.last_step();
+        self.prev_pos = self.pos;
+        let dir = field.direction([
+            self.pos.x as f64 / noise_scale,
+            self.pos.y as f64 / noise_scale,
+            0.0,
+            time as f64 / 25.0,
+        ]);
+        self.step(dir * pace, target, target_accel, Pos::new(0.0, 0.0), dt);
+        // update ttl
+        self.ttl -= dt;
+        self.age += dt;
+    }
+
+    // Move along the noise direction `dir`, towards `target` and by `steer`.
+    // Without inertia that's a fixed step in the noise direction, then a
+    // proportional step in the target direction; with it, the same steps are
+    // the velocity (at the reference frame rate) the agent is pushed towards.
+    fn step(&mut self, dir: Pos, target: Pos, target_accel: f32, steer: Pos, dt: f32) {
+        let noise_step = dir * self.step_size;
+        if !self.motion.inertia {
+            self.pos = self.pos + noise_step;
+            self.pos = self.pos + (target - self.pos) * target_accel + steer;
+            return;
+        }
+        let m = self.motion;
+        let step = noise_step + (target - (self.pos + noise_step)) * target_accel + steer;
+        // drag pulls velocity towards the push exponentially, worked out
+        // exactly rather than by an Euler step, which would overshoot and
+        // blow up once drag * dt / mass passes 2; the distance moved is that
+        // velocity's integral over the step, so one long step lands where two
+        // short ones would
+        let target_vel = step * REFERENCE_FPS;
+        let rate = m.drag / m.mass;
+        let decay = (-rate * dt).exp();
+        let settling = match rate > 0.0 {
+            true => (1.0 - decay) / rate,
+            false => dt,
+        };
+        let moved = target_vel * dt + (self.vel - target_vel) * settling;
+        self.vel = target_vel + (self.vel - target_vel) * decay;
+        let speed = self.vel.magnitude();
+        if speed > m.max_speed {
+            self.vel = self.vel * (m.max_speed / speed);
+        }
+        let dist = moved.magnitude();
+        let moved = match dist > m.max_speed * dt {
+            true => moved * (m.max_speed * dt / dist),
+            false => moved,
+        };
+        self.pos = self.pos + moved;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EAST: Pos = Pos { x: 1.0, y: 0.0 };
+    const STILL: Pos = Pos { x: 0.0, y: 0.0 };
+
+    fn moving(step_size: f32, max_speed: f32) -> Agent {
+        let motion = motion::Settings {
+            inertia: true,
+            max_speed,
+            ..Default::default()
+        };
+        let win = Rect::from_w_h(400.0, 400.0);
+        Agent::new(win, true, step_size, motion, &mut rng::from_seed(1))
+    }
+
+    // pushed east by the noise alone, for `steps` steps of `dt`
+    fn push(agent: &mut Agent, steps: usize, dt: f32) {
+        for _ in 0..steps {
+            agent.step(EAST, agent.pos, 0.0, STILL, dt);
+        }
+    }
+
+    #[test]
+    fn drag_settles_at_the_step_size() {
+        // at whatever rate it's stepped, it ends up at the speed it'd have
+        // stepped at without inertia
+        for fps in [30, 60, 144] {
+            let mut agent = moving(2.0, 1500.0);
+            push(&mut agent, fps * 5, 1.0 / fps as f32);
+            let expected = 2.0 * REFERENCE_FPS;
+            assert!((agent.vel.x - expected).abs() < 0.01, "{:?}", agent.vel);
+            assert!(agent.vel.y.abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn never_goes_past_max_speed() {
+        let mut agent = moving(100.0, 900.0);
+        for _ in 0..120 {
+            let before = agent.pos;
+            push(&mut agent, 1, 1.0 / 60.0);
+            assert!(agent.vel.magnitude() <= 900.0 + 1e-3);
+            assert!((agent.pos - before).magnitude() <= 900.0 / 60.0 + 1e-3);
+        }
+        // pushed well past it, it's held right at it
+        assert!((agent.vel.magnitude() - 900.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn one_long_step_lands_where_two_short_ones_do() {
+        // from a standstill, and again once moving
+        let (mut long, mut short) = (moving(2.0, 1500.0), moving(2.0, 1500.0));
+        for _ in 0..10 {
+            push(&mut long, 1, 1.0 / 30.0);
+            push(&mut short, 2, 1.0 / 60.0);
+            assert!(
+                (long.pos - short.pos).magnitude() < 1e-3,
+                "{:?} {:?}",
+                long.pos,
+                short.pos
+            );
+            assert!((long.vel - short.vel).magnitude() < 1e-2);
         }
     }
 }
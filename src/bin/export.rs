@@ -0,0 +1,100 @@
+// Run a visualiser headlessly for a number of frames, then export its geometry
+// as vector graphics.
+
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use duststorm::cli::{self, SimArgs, Sketch};
+use duststorm::plot::{PlotSettings, Recorder};
+use duststorm::pos::Pos;
+use duststorm::{perlin, perlin2, voronoi};
+
+/// Run a visualiser headlessly, then export it as vector graphics: the voronoi
+/// cells as an SVG, or the perlin agents' trails as SVG, HPGL and G-code for
+/// pen plotters
+#[derive(Parser)]
+struct Args {
+    /// Which visualiser to export
+    #[arg(value_enum)]
+    sketch: Sketch,
+
+    /// Number of steps to simulate (and, for perlin, record), at --sim-rate
+    #[arg(long, default_value_t = 300)]
+    frames: usize,
+
+    /// Output path; the extension is replaced per format [default: the sketch name]
+    #[arg(long)]
+    out: Option<PathBuf>,
+
+    /// Mark each voronoi site
+    #[arg(long)]
+    sites: bool,
+
+    #[command(flatten)]
+    sim: SimArgs,
+}
+
+fn main() {
+    let args = Args::parse();
+    let out = args
+        .out
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(args.sketch.to_string()));
+
+    let win = nannou::geom::Rect::from_w_h(args.sim.size.width as f32, args.sim.size.height as f32);
+    let seed = cli::or_exit(args.sim.seed());
+    println!("seed {}", seed);
+    let dt = 1.0 / args.sim.sim_rate;
+
+    match args.sketch {
+        Sketch::Voronoi => {
+            let mut sim = voronoi::Sim::new(win, seed, cli::or_exit(args.sim.voronoi()));
+            (0..args.frames).for_each(|_| sim.update());
+            let path = out.with_extension("svg");
+            sim.to_svg(args.sites)
+                .save(&path)
+                .unwrap_or_else(|e| panic!("couldn't write {}: {}", path.display(), e));
+        }
+        Sketch::Perlin => {
+            let mut sim = perlin::Sim::new(win, seed, cli::or_exit(args.sim.perlin()));
+            let mut recorder = Recorder::new(win);
+            for frame in 0..args.frames {
+                sim.update(frame as f32 * dt, dt, Pos::new(0.0, 0.0));
+                recorder.record(&sim.agents);
+            }
+            save_plot(recorder, &out);
+        }
+        Sketch::Perlin2 => {
+            let mut sim = perlin2::Sim::new(win, seed, cli::or_exit(args.sim.perlin2()));
+            let mut recorder = Recorder::new(win);
+            for frame in 0..args.frames {
+                sim.update(frame as f32 * dt, dt);
+                recorder.record(&sim.agents);
+            }
+            save_plot(recorder, &out);
+        }
+    }
+    println!(
+        "exported {} after {} frames to {}",
+        args.sketch,
+        args.frames,
+        out.display()
+    );
+}
+
+// simplify and order the recorded trails, then write them as SVG, HPGL and
+// G-code
+fn save_plot(recorder: Recorder, stem: &Path) {
+    let settings = PlotSettings::default();
+    let mut plot = recorder.finish();
+    let travel_before = plot.pen_up_distance();
+    plot.prepare(&settings);
+    println!(
+        "{} paths, pen-up travel {:.0}px -> {:.0}px",
+        plot.paths.len(),
+        travel_before,
+        plot.pen_up_distance()
+    );
+    plot.save_all(stem, &settings)
+        .unwrap_or_else(|e| panic!("couldn't write {}: {}", stem.display(), e));
+}
@@ -0,0 +1,179 @@
+// Agents that move following a target, while being pushed around by Perlin noise.
+
+mod ui;
+
+use std::sync::OnceLock;
+
+use clap::Parser;
+use duststorm::cli::{self, SimArgs};
+use duststorm::clock::FixedStep;
+use duststorm::painter::DrawPainter;
+use duststorm::perlin::{Settings, Sim};
+use duststorm::plot::Recorder;
+use duststorm::pos::Pos;
+use duststorm::preset::PresetWatcher;
+use nannou::prelude::*;
+
+/// A cloud of agents pushing through 3-dimensional Perlin noise, chasing a
+/// moving target
+#[derive(Parser)]
+struct Args {
+    #[command(flatten)]
+    sim: SimArgs,
+}
+
+// the flags, and the settings and seed they come to, worked out once up front
+// in `main` so that `--help`, bad flags and bad presets are reported before a
+// window opens
+static START: OnceLock<(SimArgs, Settings, u64)> = OnceLock::new();
+
+fn main() {
+    let args = Args::parse().sim;
+    let settings = cli::or_exit(args.perlin());
+    let seed = cli::or_exit(args.seed());
+    START.get_or_init(|| (args, settings, seed));
+    nannou::app(model).update(update).run();
+}
+
+struct Model {
+    args: SimArgs,
+    sim: Sim,
+    clock: FixedStep,
+    draw_target: bool,
+    recorder: Option<Recorder>,
+    preset: Option<PresetWatcher>,
+    // whether to show the octave settings
+    readout: bool,
+}
+
+fn model(app: &App) -> Model {
+    let (args, settings, seed) = START.get().expect("set in main").clone();
+    println!("seed {}", seed);
+    app.new_window()
+        .size(args.size.width, args.size.height)
+        .view(view)
+        .key_released(key_released)
+        .build()
+        .unwrap();
+    Model {
+        sim: Sim::new(app.window_rect(), seed, settings),
+        clock: args.clock(),
+        draw_target: false,
+        recorder: None,
+        preset: ui::watch_preset(args.preset.as_deref()),
+        readout: false,
+        args,
+    }
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if model.preset.as_ref().is_some_and(PresetWatcher::changed) {
+        match model.args.perlin() {
+            Ok(settings) => {
+                model.sim.apply_settings(settings);
+                println!("reloaded preset");
+            }
+            Err(e) => eprintln!("couldn't reload preset: {}", e),
+        }
+    }
+    let mouse = Pos::new(app.mouse.x, app.mouse.y);
+    for time in model.clock.advance(update.since_last.as_secs_f32()) {
+        model.sim.update(time, model.clock.dt, mouse);
+        if let Some(recorder) = &mut model.recorder {
+            recorder.record(&model.sim.agents);
+        }
+    }
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    let mut painter = DrawPainter::new(&draw, app.window_rect());
+    let alpha = model.clock.alpha();
+    model.sim.paint(&mut painter, model.draw_target, alpha);
+    if model.readout {
+        let text = model.sim.settings.field.octaves_readout();
+        ui::draw_readout(&draw, app.window_rect(), &text);
+    }
+    draw.to_frame(app, &frame).unwrap();
+}
+
+fn key_released(app: &App, model: &mut Model, key: Key) {
+    // the number keys step the octave settings, showing the readout so the
+    // change can be seen
+    if let Some(field) = ui::step_octaves(key, &model.sim.settings.field) {
+        model.sim.set_field(field);
+        model.readout = true;
+        return;
+    }
+    match key {
+        Key::D => {
+            let mut modes = model.sim.target_modes();
+            modes[0] = modes[0].next();
+            model.sim.set_targets(modes);
+        }
+        Key::A => {
+            let assignment = model.sim.settings.assignment.next();
+            model.sim.set_assignment(assignment);
+        }
+        Key::Equals => {
+            let mut modes = model.sim.target_modes();
+            modes.push(modes[modes.len() - 1].next());
+            model.sim.set_targets(modes);
+        }
+        Key::Minus => {
+            let mut modes = model.sim.target_modes();
+            if modes.len() > 1 {
+                modes.pop();
+                model.sim.set_targets(modes);
+            }
+        }
+        Key::C => {
+            let settings = &mut model.sim.settings;
+            settings.color_mode = settings.color_mode.next();
+        }
+        Key::G => {
+            let colors = &mut model.sim.settings.colors;
+            colors.input = colors.input.next();
+        }
+        Key::T => {
+            model.draw_target = match model.draw_target {
+                false => true,
+                true => false,
+            };
+        }
+        Key::B => model.sim.settings.flock.enabled = !model.sim.settings.flock.enabled,
+        Key::Space => model.sim.reseed(),
+        Key::V => {
+            let mut motion = model.sim.settings.motion;
+            motion.inertia = !motion.inertia;
+            model.sim.set_motion(motion);
+        }
+        Key::M => {
+            let mut field = model.sim.settings.field.clone();
+            field.mode = field.mode.next();
+            model.sim.set_field(field);
+        }
+        Key::N => {
+            let mut field = model.sim.settings.field.clone();
+            field.noise = field.noise.next();
+            model.sim.set_field(field);
+        }
+        Key::F => {
+            let mut field = model.sim.settings.field.clone();
+            field.fractal = field.fractal.next();
+            model.sim.set_field(field);
+        }
+        Key::W => {
+            let mut field = model.sim.settings.field.clone();
+            field.warp_levels = (field.warp_levels + 1) % 3;
+            model.sim.set_field(field);
+        }
+        Key::I => model.readout = !model.readout,
+        Key::R => model.sim.reset_agents(),
+        Key::P => {
+            let stem = format!("perlin_{}_{:05}", model.sim.seed, app.elapsed_frames());
+            ui::toggle_recording(&mut model.recorder, model.sim.win, &stem);
+        }
+        _other_key => {}
+    }
+}
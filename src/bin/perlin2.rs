@@ -0,0 +1,188 @@
+// Agents that move following a target, while being pushed around by Perlin noise.
+
+mod ui;
+
+use std::sync::OnceLock;
+
+use clap::Parser;
+use duststorm::cli::{self, SimArgs};
+use duststorm::clock::FixedStep;
+use duststorm::color::ColorMode;
+use duststorm::painter::DrawPainter;
+use duststorm::perlin2::{Settings, Sim};
+use duststorm::plot::Recorder;
+use duststorm::preset::PresetWatcher;
+use nannou::prelude::*;
+
+/// A dense field of short-lived agents drifting through slowly evolving Perlin
+/// noise
+#[derive(Parser)]
+struct Args {
+    #[command(flatten)]
+    sim: SimArgs,
+}
+
+// the flags, and the settings and seed they come to, worked out once up front
+// in `main` so that `--help`, bad flags and bad presets are reported before a
+// window opens
+static START: OnceLock<(SimArgs, Settings, u64)> = OnceLock::new();
+
+fn main() {
+    let args = Args::parse().sim;
+    let settings = cli::or_exit(args.perlin2());
+    let seed = cli::or_exit(args.seed());
+    START.get_or_init(|| (args, settings, seed));
+    nannou::app(model).update(update).run();
+}
+
+struct Model {
+    args: SimArgs,
+    sim: Sim,
+    clock: FixedStep,
+    // the steps taken since the last frame, each drawn as it's taken since
+    // the trails build up step by step, then on to partway through the next
+    draw: Draw,
+    recorder: Option<Recorder>,
+    preset: Option<PresetWatcher>,
+    // whether to show the octave settings
+    readout: bool,
+}
+
+fn model(app: &App) -> Model {
+    let (args, settings, seed) = START.get().expect("set in main").clone();
+    println!("seed {}", seed);
+    app.new_window()
+        .size(args.size.width, args.size.height)
+        .view(view)
+        .key_released(key_released)
+        .build()
+        .unwrap();
+    Model {
+        sim: Sim::new(app.window_rect(), seed, settings),
+        clock: args.clock(),
+        draw: Draw::new(),
+        recorder: None,
+        preset: ui::watch_preset(args.preset.as_deref()),
+        readout: false,
+        args,
+    }
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    if model.preset.as_ref().is_some_and(PresetWatcher::changed) {
+        match model.args.perlin2() {
+            Ok(settings) => {
+                model.sim.apply_settings(settings);
+                println!("reloaded preset");
+            }
+            Err(e) => eprintln!("couldn't reload preset: {}", e),
+        }
+    }
+    for time in model.clock.advance(update.since_last.as_secs_f32()) {
+        model.sim.update(time, model.clock.dt);
+        if let Some(recorder) = &mut model.recorder {
+            recorder.record(&model.sim.agents);
+        }
+        // a long exposure is drawn whole in `view` instead
+        if model.sim.accumulator().is_none() {
+            model
+                .sim
+                .paint_step(&mut DrawPainter::new(&model.draw, app.window_rect()));
+        }
+    }
+    if model.sim.accumulator().is_none() {
+        let mut painter = DrawPainter::new(&model.draw, app.window_rect());
+        model.sim.paint(&mut painter, model.clock.alpha());
+    }
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = &model.draw;
+    if let Some(accumulator) = model.sim.accumulator() {
+        let accum = &model.sim.settings.accum;
+        let pixels = accumulator.to_rgba8(accum.exposure, accum.tone_map);
+        let image =
+            nannou::image::RgbaImage::from_raw(accumulator.width(), accumulator.height(), pixels)
+                .expect("accumulator size mismatch");
+        let texture =
+            wgpu::Texture::from_image(app, &nannou::image::DynamicImage::ImageRgba8(image));
+        draw.texture(&texture).wh(app.window_rect().wh());
+    }
+    if model.readout {
+        let text = model.sim.settings.field.octaves_readout();
+        ui::draw_readout(draw, app.window_rect(), &text);
+    }
+    draw.to_frame(app, &frame).unwrap();
+    draw.reset();
+}
+
+fn key_released(app: &App, model: &mut Model, key: Key) {
+    // the number keys step the octave settings, showing the readout so the
+    // change can be seen
+    if let Some(field) = ui::step_octaves(key, &model.sim.settings.field) {
+        model.sim.set_field(field);
+        model.readout = true;
+        return;
+    }
+    match key {
+        Key::Space => model.sim.reseed(),
+        Key::A => {
+            let mut accum = model.sim.settings.accum.clone();
+            accum.enabled = !accum.enabled;
+            model.sim.set_accum(accum);
+        }
+        Key::T => {
+            let mut accum = model.sim.settings.accum.clone();
+            accum.tone_map = accum.tone_map.next();
+            model.sim.set_accum(accum);
+        }
+        // through the palettes, then back to colouring by ttl
+        Key::C => {
+            let mode = &mut model.sim.settings.color_mode;
+            *mode = match *mode {
+                None => Some(ColorMode::White),
+                Some(ColorMode::File) => None,
+                Some(m) => Some(m.next()),
+            };
+        }
+        Key::G => {
+            let colors = &mut model.sim.settings.colors;
+            colors.input = colors.input.next();
+        }
+        Key::V => {
+            let mut motion = model.sim.settings.motion;
+            motion.inertia = !motion.inertia;
+            model.sim.set_motion(motion);
+        }
+        Key::M => {
+            let mut field = model.sim.settings.field.clone();
+            field.mode = field.mode.next();
+            model.sim.set_field(field);
+        }
+        Key::N => {
+            let mut field = model.sim.settings.field.clone();
+            field.noise = field.noise.next();
+            model.sim.set_field(field);
+        }
+        Key::F => {
+            let mut field = model.sim.settings.field.clone();
+            field.fractal = field.fractal.next();
+            model.sim.set_field(field);
+        }
+        Key::W => {
+            let mut field = model.sim.settings.field.clone();
+            field.warp_levels = (field.warp_levels + 1) % 3;
+            model.sim.set_field(field);
+        }
+        Key::I => model.readout = !model.readout,
+        Key::R => {
+            model.sim.win = app.window_rect();
+            model.sim.reset_agents();
+        }
+        Key::P => {
+            let stem = format!("perlin2_{}_{:05}", model.sim.seed, app.elapsed_frames());
+            ui::toggle_recording(&mut model.recorder, model.sim.win, &stem);
+        }
+        _other_key => {}
+    }
+}
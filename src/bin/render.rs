@@ -0,0 +1,112 @@
+// Render a visualiser headlessly into a numbered sequence of PNG frames, with
+// no window or GPU required.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use duststorm::canvas::Canvas;
+use duststorm::cli::{self, SimArgs, Sketch};
+use duststorm::clock::FixedStep;
+use duststorm::pos::Pos;
+use duststorm::{perlin, perlin2, voronoi};
+
+/// Render a visualiser on the CPU into a numbered PNG sequence, with no window
+/// or GPU required
+#[derive(Parser)]
+struct Args {
+    /// Which visualiser to render
+    #[arg(value_enum)]
+    sketch: Sketch,
+
+    /// Number of frames to render
+    #[arg(long, default_value_t = 300)]
+    frames: usize,
+
+    /// Frames per second of the rendered animation; the simulation still
+    /// steps at --sim-rate
+    #[arg(long, default_value_t = 60.0, value_parser = cli::positive_f32)]
+    fps: f32,
+
+    /// Directory to write frame_00000.png, ... into
+    #[arg(long, default_value = "frames")]
+    out: PathBuf,
+
+    #[command(flatten)]
+    sim: SimArgs,
+}
+
+fn main() {
+    let args = Args::parse();
+    fs::create_dir_all(&args.out).expect("couldn't create output directory");
+
+    let mut canvas = Canvas::new(args.sim.size.width, args.sim.size.height);
+    let win = canvas.rect();
+    let seed = cli::or_exit(args.sim.seed());
+    println!("seed {}", seed);
+    // no frame is ever late offline, so every step is taken
+    let mut clock = FixedStep::new(args.sim.sim_rate, u32::MAX);
+    let frame_time = 1.0 / args.fps;
+
+    match args.sketch {
+        Sketch::Perlin => {
+            let mut sim = perlin::Sim::new(win, seed, cli::or_exit(args.sim.perlin()));
+            for frame in 0..args.frames {
+                for time in clock.advance(frame_time) {
+                    sim.update(time, clock.dt, Pos::new(0.0, 0.0));
+                }
+                sim.paint(&mut canvas, false, clock.alpha());
+                save_frame(&canvas, &args.out, frame);
+            }
+        }
+        Sketch::Perlin2 => {
+            let mut sim = perlin2::Sim::new(win, seed, cli::or_exit(args.sim.perlin2()));
+            for frame in 0..args.frames {
+                for time in clock.advance(frame_time) {
+                    sim.update(time, clock.dt);
+                    if sim.accumulator().is_none() {
+                        sim.paint_step(&mut canvas);
+                    }
+                }
+                match sim.accumulator() {
+                    Some(accumulator) => {
+                        let path = frame_path(&args.out, frame);
+                        let accum = &sim.settings.accum;
+                        accumulator
+                            .save_png(&path, accum.exposure, accum.tone_map)
+                            .unwrap_or_else(|e| panic!("couldn't write {}: {}", path.display(), e));
+                    }
+                    None => {
+                        sim.paint(&mut canvas, clock.alpha());
+                        save_frame(&canvas, &args.out, frame);
+                    }
+                }
+            }
+        }
+        Sketch::Voronoi => {
+            let mut sim = voronoi::Sim::new(win, seed, cli::or_exit(args.sim.voronoi()));
+            for frame in 0..args.frames {
+                clock.advance(frame_time).for_each(|_| sim.update());
+                sim.paint(&mut canvas, false, clock.alpha());
+                save_frame(&canvas, &args.out, frame);
+            }
+        }
+    }
+    println!(
+        "rendered {} frames of {} to {}",
+        args.frames,
+        args.sketch,
+        args.out.display()
+    );
+}
+
+fn frame_path(out_dir: &Path, frame: usize) -> PathBuf {
+    out_dir.join(format!("frame_{:05}.png", frame))
+}
+
+fn save_frame(canvas: &Canvas, out_dir: &Path, frame: usize) {
+    let path = frame_path(out_dir, frame);
+    canvas
+        .save_png(&path)
+        .unwrap_or_else(|e| panic!("couldn't write {}: {}", path.display(), e));
+}
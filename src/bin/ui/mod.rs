@@ -0,0 +1,75 @@
+// The parts of the windowed sketches' front-ends they have in common: the
+// octave readout and the keys that step it, recording trails for the plotter
+// and watching the preset file. Each sketch uses only some of them.
+
+#![allow(dead_code)]
+
+use std::path::Path;
+
+use duststorm::field::{self, OctaveControl};
+use duststorm::plot::{PlotSettings, Recorder};
+use duststorm::preset::PresetWatcher;
+use nannou::color::{BLACK, WHITE};
+use nannou::event::Key;
+use nannou::geom::{pt2, Rect};
+use nannou::Draw;
+
+// the octave settings in the top left corner, over a black box so they stay
+// legible on top of the trails
+pub fn draw_readout(draw: &Draw, win: Rect, text: &str) {
+    let (w, h) = (460.0, 20.0);
+    let xy = pt2(win.left() + w / 2.0 + 10.0, win.top() - h / 2.0 - 10.0);
+    draw.rect().xy(xy).w_h(w, h).color(BLACK);
+    draw.text(text)
+        .xy(xy)
+        .w_h(w - 10.0, h)
+        .left_justify()
+        .font_size(12)
+        .color(WHITE);
+}
+
+// `field` with one of its octave settings nudged, if `key` is one of the
+// number keys that step them: 1 and 2 for the octaves, 3 and 4 lacunarity, 5
+// and 6 persistence, and 7 and 8 time speed, each down then up
+pub fn step_octaves(key: Key, field: &field::Settings) -> Option<field::Settings> {
+    let (control, up) = match key {
+        Key::Key1 => (OctaveControl::Octaves, false),
+        Key::Key2 => (OctaveControl::Octaves, true),
+        Key::Key3 => (OctaveControl::Lacunarity, false),
+        Key::Key4 => (OctaveControl::Lacunarity, true),
+        Key::Key5 => (OctaveControl::Persistence, false),
+        Key::Key6 => (OctaveControl::Persistence, true),
+        Key::Key7 => (OctaveControl::TimeSpeed, false),
+        Key::Key8 => (OctaveControl::TimeSpeed, true),
+        _ => return None,
+    };
+    let mut field = field.clone();
+    field.step(control, up);
+    Some(field)
+}
+
+// start recording trails for the plotter in a window `win`, or stop and save
+// them as `stem`.svg, .hpgl and .gcode
+pub fn toggle_recording(recorder: &mut Option<Recorder>, win: Rect, stem: &str) {
+    match recorder.take() {
+        None => *recorder = Some(Recorder::new(win)),
+        Some(recording) => {
+            let settings = PlotSettings::default();
+            let mut plot = recording.finish();
+            plot.prepare(&settings);
+            match plot.save_all(stem, &settings) {
+                Ok(()) => println!("saved {}.svg/.hpgl/.gcode", stem),
+                Err(e) => eprintln!("couldn't save {}: {}", stem, e),
+            }
+        }
+    }
+}
+
+// start watching the preset file, if there is one; a sketch whose preset
+// can't be watched still runs, just without reloading it
+pub fn watch_preset(path: Option<&Path>) -> Option<PresetWatcher> {
+    let path = path?;
+    PresetWatcher::new(path)
+        .map_err(|e| eprintln!("couldn't watch {}: {}", path.display(), e))
+        .ok()
+}
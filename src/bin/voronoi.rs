@@ -0,0 +1,120 @@
+// Draw a collection of voronoi cells, animated while their sites randomly
+// wander around.
+
+mod ui;
+
+use std::sync::OnceLock;
+
+use clap::Parser;
+use duststorm::cli::{self, SimArgs};
+use duststorm::clock::FixedStep;
+use duststorm::painter::DrawPainter;
+use duststorm::pos::Pos;
+use duststorm::preset::PresetWatcher;
+use duststorm::voronoi::{Settings, Sim};
+use nannou::prelude::*;
+
+/// Voronoi cells drawn around a cloud of wandering agents
+#[derive(Parser)]
+struct Args {
+    #[command(flatten)]
+    sim: SimArgs,
+}
+
+// the flags, and the settings and seed they come to, worked out once up front
+// in `main` so that `--help`, bad flags and bad presets are reported before a
+// window opens
+static START: OnceLock<(SimArgs, Settings, u64)> = OnceLock::new();
+
+fn main() {
+    let args = Args::parse().sim;
+    let settings = cli::or_exit(args.voronoi());
+    let seed = cli::or_exit(args.seed());
+    START.get_or_init(|| (args, settings, seed));
+    nannou::app(model).update(update).run();
+}
+
+struct Model {
+    args: SimArgs,
+    sim: Sim,
+    clock: FixedStep,
+    draw_sites: bool,
+    preset: Option<PresetWatcher>,
+}
+
+fn model(app: &App) -> Model {
+    let (args, settings, seed) = START.get().expect("set in main").clone();
+    println!("seed {}", seed);
+    app.new_window()
+        .size(args.size.width, args.size.height)
+        .view(view)
+        .key_released(key_released)
+        .mouse_released(mouse_released)
+        .build()
+        .unwrap();
+    let win = app.window_rect();
+    Model {
+        sim: Sim::new(win, seed, settings),
+        clock: args.clock(),
+        draw_sites: false,
+        preset: ui::watch_preset(args.preset.as_deref()),
+        args,
+    }
+}
+
+fn update(_app: &App, model: &mut Model, update: Update) {
+    if model.preset.as_ref().is_some_and(PresetWatcher::changed) {
+        match model.args.voronoi() {
+            Ok(settings) => {
+                model.sim.apply_settings(settings);
+                println!("reloaded preset");
+            }
+            Err(e) => eprintln!("couldn't reload preset: {}", e),
+        }
+    }
+    for _ in model.clock.advance(update.since_last.as_secs_f32()) {
+        model.sim.update();
+    }
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    let mut painter = DrawPainter::new(&draw, app.window_rect());
+    model
+        .sim
+        .paint(&mut painter, model.draw_sites, model.clock.alpha());
+    draw.to_frame(app, &frame).unwrap();
+}
+
+fn key_released(app: &App, model: &mut Model, key: Key) {
+    match key {
+        Key::T => model.sim.update_mode = model.sim.update_mode.next(),
+        Key::R => model.sim.reset_agents(),
+        Key::P => model.draw_sites = !model.draw_sites,
+        Key::F => {
+            let fill = &mut model.sim.settings.fill;
+            fill.mode = fill.mode.next();
+        }
+        Key::C => {
+            let fill = &mut model.sim.settings.fill;
+            fill.palette = fill.palette.next();
+        }
+        Key::S => {
+            let path = format!("voronoi_{}_{:05}.svg", model.sim.seed, app.elapsed_frames());
+            match model.sim.to_svg(model.draw_sites).save(&path) {
+                Ok(()) => println!("saved {}", path),
+                Err(e) => eprintln!("couldn't save {}: {}", path, e),
+            }
+        }
+        _other_key => {}
+    }
+}
+
+fn mouse_released(app: &App, model: &mut Model, mouse: MouseButton) {
+    let mouse_pos = Pos::new(app.mouse.x, app.mouse.y);
+    match mouse {
+        MouseButton::Left => model.sim.add_site(mouse_pos),
+        MouseButton::Right => model.sim.remove_site(mouse_pos),
+        _ => {}
+    }
+}
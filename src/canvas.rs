@@ -0,0 +1,205 @@
+// A software rasterizer, for rendering the visualisers without a window or a
+// GPU.
+//
+// Uses the same coordinate system as a nannou window: the origin is the
+// canvas centre and y points up. Shapes are antialiased by their coverage of
+// each pixel and alpha-blended over what's already there.
+
+use std::path::Path;
+
+use nannou::geom::Rect;
+
+use crate::color::{Color, BLACK};
+use crate::painter::Painter;
+use crate::pos::Pos;
+
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    // row-major, top row first, non-premultiplied RGBA
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Canvas {
+    // a new canvas, cleared to opaque black
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut canvas = Canvas {
+            width,
+            height,
+            pixels: vec![[0.0; 4]; (width * height) as usize],
+        };
+        canvas.background(BLACK);
+        canvas
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // the area covered by the canvas, as a window rect
+    pub fn rect(&self) -> Rect {
+        Rect::from_w_h(self.width as f32, self.height as f32)
+    }
+
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|px| px.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+            .collect()
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> image::ImageResult<()> {
+        image::save_buffer(
+            path,
+            &self.to_rgba8(),
+            self.width,
+            self.height,
+            image::ColorType::Rgba8,
+        )
+    }
+
+    // canvas coordinates to (fractional) pixel coordinates
+    fn to_pixel(&self, pos: Pos) -> Pos {
+        Pos::new(
+            pos.x + self.width as f32 / 2.0,
+            self.height as f32 / 2.0 - pos.y,
+        )
+    }
+
+    // source-over blend `color` onto one pixel, scaled by its coverage
+    fn blend(&mut self, x: usize, y: usize, color: Color, coverage: f32) {
+        let alpha = color.alpha * coverage;
+        if alpha <= 0.0 {
+            return;
+        }
+        let px = &mut self.pixels[y * self.width as usize + x];
+        let dst_weight = px[3] * (1.0 - alpha);
+        let out_alpha = alpha + dst_weight;
+        let src = [color.red, color.green, color.blue];
+        for (c, s) in px.iter_mut().zip(src) {
+            *c = (s * alpha + *c * dst_weight) / out_alpha;
+        }
+        px[3] = out_alpha;
+    }
+
+    // blend `color` over every pixel within the pixel-space bounds, weighted
+    // by `coverage(pixel_centre)`
+    fn fill<F>(&mut self, min: Pos, max: Pos, color: Color, coverage: F)
+    where
+        F: Fn(Pos) -> f32,
+    {
+        let x0 = min.x.floor().max(0.0) as usize;
+        let y0 = min.y.floor().max(0.0) as usize;
+        let x1 = (max.x.ceil().max(0.0) as usize).min(self.width as usize);
+        let y1 = (max.y.ceil().max(0.0) as usize).min(self.height as usize);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let c = coverage(Pos::new(x as f32 + 0.5, y as f32 + 0.5));
+                self.blend(x, y, color, c.clamp(0.0, 1.0));
+            }
+        }
+    }
+}
+
+// distance from `p` to the segment `a`-`b`
+fn segment_distance(p: Pos, a: Pos, b: Pos) -> f32 {
+    let ab = b - a;
+    let len2 = ab.x * ab.x + ab.y * ab.y;
+    let t = if len2 > 0.0 {
+        (((p.x - a.x) * ab.x + (p.y - a.y) * ab.y) / len2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (p - (a + ab * t)).magnitude()
+}
+
+// whether `p` is inside the polygon `points`, by the even-odd rule
+fn inside(p: Pos, points: &[Pos]) -> bool {
+    let mut inside = false;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        if (a.y > p.y) != (b.y > p.y) && p.x < a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+impl Painter for Canvas {
+    fn background(&mut self, color: Color) {
+        let px = [color.red, color.green, color.blue, 1.0];
+        self.pixels.iter_mut().for_each(|p| *p = px);
+    }
+
+    fn fade(&mut self, color: Color) {
+        let (w, h) = (self.width as f32, self.height as f32);
+        self.fill(Pos::new(0.0, 0.0), Pos::new(w, h), color, |_| 1.0);
+    }
+
+    fn point(&mut self, pos: Pos, radius: f32, color: Color) {
+        let centre = self.to_pixel(pos);
+        let reach = Pos::new(radius + 1.0, radius + 1.0);
+        self.fill(centre - reach, centre + reach, color, |p| {
+            radius + 0.5 - (p - centre).magnitude()
+        });
+    }
+
+    fn line(&mut self, start: Pos, end: Pos, weight: f32, color: Color) {
+        let (a, b) = (self.to_pixel(start), self.to_pixel(end));
+        let half = weight / 2.0;
+        let reach = half + 1.0;
+        let min = Pos::new(a.x.min(b.x) - reach, a.y.min(b.y) - reach);
+        let max = Pos::new(a.x.max(b.x) + reach, a.y.max(b.y) + reach);
+        self.fill(min, max, color, |p| half + 0.5 - segment_distance(p, a, b));
+    }
+
+    // each segment in the colour halfway between its ends', which on the
+    // short segments of a trail is close enough to a blend along it
+    fn polyline(&mut self, points: &[(Pos, Color)], weight: f32) {
+        for pair in points.windows(2) {
+            let ((start, a), (end, b)) = (pair[0], pair[1]);
+            let color = Color::new(
+                (a.red + b.red) / 2.0,
+                (a.green + b.green) / 2.0,
+                (a.blue + b.blue) / 2.0,
+                (a.alpha + b.alpha) / 2.0,
+            );
+            self.line(start, end, weight, color);
+        }
+    }
+
+    fn polyline_closed(&mut self, points: &[Pos], weight: f32, color: Color) {
+        for (i, start) in points.iter().enumerate() {
+            let end = points[(i + 1) % points.len()];
+            self.line(*start, end, weight, color);
+        }
+    }
+
+    fn polygon(&mut self, points: &[Pos], color: Color) {
+        if points.len() < 3 {
+            return;
+        }
+        let points: Vec<Pos> = points.iter().map(|p| self.to_pixel(*p)).collect();
+        let (min, max) = points.iter().fold((points[0], points[0]), |(min, max), p| {
+            (
+                Pos::new(min.x.min(p.x), min.y.min(p.y)),
+                Pos::new(max.x.max(p.x), max.y.max(p.y)),
+            )
+        });
+        let reach = Pos::new(1.0, 1.0);
+        // half covered on the outline, fading out over a pixel either side
+        self.fill(min - reach, max + reach, color, |p| {
+            let edge = (0..points.len())
+                .map(|i| segment_distance(p, points[i], points[(i + 1) % points.len()]))
+                .fold(f32::INFINITY, f32::min);
+            match inside(p, &points) {
+                true => 0.5 + edge,
+                false => 0.5 - edge,
+            }
+        });
+    }
+}
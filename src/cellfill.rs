@@ -0,0 +1,384 @@
+// Filling voronoi cells with colour: what picks each cell's colour, and how
+// cells are outlined, inset from their edges and rounded off at the corners.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use palette::Srgb;
+use serde::{Deserialize, Serialize};
+
+use crate::color::ColorMode;
+use crate::colormap::{self, Hex};
+use crate::picture::Picture;
+use crate::pos::Pos;
+use crate::preset::Error;
+
+// colours the graph fill has to go round, which is all a planar graph like a
+// Voronoi diagram's needs when coloured smallest-last
+pub const GRAPH_COLORS: usize = 6;
+// lines each rounded corner is flattened into
+const CORNER_SEGMENTS: usize = 8;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fill {
+    // outlines only
+    None,
+    // along the palette by area, from nothing to `area_range` times the
+    // average
+    Area,
+    // by how many neighbours a cell has, from 3 to 9
+    Neighbors,
+    // by how fast its site is moving
+    Velocity,
+    // by Perlin noise under its site
+    Noise,
+    // the picture's colour under its site, like stained glass
+    Picture,
+    // so that no two neighbouring cells share a colour
+    Graph,
+}
+
+impl Fill {
+    // the next fill in the F-key cycle
+    pub fn next(self) -> Fill {
+        match self {
+            Fill::None => Fill::Area,
+            Fill::Area => Fill::Neighbors,
+            Fill::Neighbors => Fill::Velocity,
+            Fill::Velocity => Fill::Noise,
+            Fill::Noise => Fill::Picture,
+            Fill::Picture => Fill::Graph,
+            Fill::Graph => Fill::None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub mode: Fill,
+    // the palette the fills besides picture are taken from, and the
+    // gradients behind it; its input isn't used, as the fill places cells
+    pub palette: ColorMode,
+    pub colors: colormap::Settings,
+    // the cell area, as a multiple of the average, and the site speed, in
+    // pixels per step, that reach the end of the palette
+    pub area_range: f32,
+    pub speed_range: f32,
+    // the size of the noise's features, in pixels
+    pub noise_scale: f64,
+    // a PNG or JPEG for the picture fill, fit inside the window
+    pub picture: Option<PathBuf>,
+    // the outline's width in pixels (0 for none) and colour
+    pub stroke_width: f32,
+    pub stroke_color: Hex,
+    // how far cells are pulled in from their edges, and the radius their
+    // corners are rounded to, in pixels
+    pub inset: f32,
+    pub rounding: f32,
+    // the decoded picture; filled in from `picture` by `load`
+    #[serde(skip)]
+    loaded: Option<Arc<Picture>>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            mode: Fill::None,
+            palette: ColorMode::Gradient,
+            colors: colormap::Settings::default(),
+            area_range: 2.0,
+            speed_range: 1.0,
+            noise_scale: 300.0,
+            picture: None,
+            stroke_width: 1.0,
+            stroke_color: Hex(Srgb::new(255, 255, 255)),
+            inset: 0.0,
+            rounding: 0.0,
+            loaded: None,
+        }
+    }
+}
+
+impl Settings {
+    // read the palette file and decode the picture, if there are any
+    pub fn load(&mut self) -> Result<(), Error> {
+        self.colors.load()?;
+        self.loaded = match &self.picture {
+            Some(path) => Some(Arc::new(Picture::open(path)?)),
+            None => None,
+        };
+        Ok(())
+    }
+
+    pub fn picture(&self) -> Option<&Picture> {
+        self.loaded.as_deref()
+    }
+
+    // a cell's outline pulled in by `inset` and rounded by `rounding`;
+    // nothing if the inset swallows it whole
+    pub fn shape(&self, cell: &[Pos]) -> Option<Vec<Pos>> {
+        let inset = inset(cell, self.inset)?;
+        Some(round(&inset, self.rounding))
+    }
+}
+
+// twice the signed area of a polygon, positive if it winds anticlockwise
+fn signed_area2(points: &[Pos]) -> f32 {
+    (0..points.len())
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            a.x * b.y - b.x * a.y
+        })
+        .sum()
+}
+
+pub fn area(points: &[Pos]) -> f32 {
+    signed_area2(points).abs() / 2.0
+}
+
+fn cross(a: Pos, b: Pos) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+fn dot(a: Pos, b: Pos) -> f32 {
+    a.x * b.x + a.y * b.y
+}
+
+// A convex polygon with each edge moved `by` pixels inwards, or nothing if
+// that leaves no polygon.
+pub fn inset(points: &[Pos], by: f32) -> Option<Vec<Pos>> {
+    if by <= 0.0 {
+        return Some(points.to_vec());
+    }
+    // corners joining edges too short to have a direction are dropped
+    let mut corners: Vec<Pos> = Vec::with_capacity(points.len());
+    for p in points {
+        if corners
+            .last()
+            .is_none_or(|last| (*p - *last).magnitude() > 1e-3)
+        {
+            corners.push(*p);
+        }
+    }
+    while corners.len() > 1 && (corners[0] - corners[corners.len() - 1]).magnitude() <= 1e-3 {
+        corners.pop();
+    }
+    let n = corners.len();
+    if n < 3 {
+        return None;
+    }
+    let inwards = signed_area2(&corners).signum();
+    // each edge's line, as a point on it and its direction
+    let lines: Vec<(Pos, Pos)> = (0..n)
+        .map(|i| {
+            let (a, b) = (corners[i], corners[(i + 1) % n]);
+            let dir = (b - a) / (b - a).magnitude();
+            (a + Pos::new(-dir.y, dir.x) * (by * inwards), dir)
+        })
+        .collect();
+    // where each edge's line meets the last one's
+    let inset: Vec<Pos> = (0..n)
+        .map(|i| {
+            let ((p, d), (q, e)) = (lines[(i + n - 1) % n], lines[i]);
+            match cross(d, e).abs() > 1e-6 {
+                true => p + d * (cross(q - p, e) / cross(d, e)),
+                false => q,
+            }
+        })
+        .collect();
+    // an edge turned back on itself means the polygon's collapsed
+    let collapsed = (0..n).any(|i| dot(inset[(i + 1) % n] - inset[i], lines[i].1) <= 0.0);
+    match collapsed {
+        true => None,
+        false => Some(inset),
+    }
+}
+
+// A polygon with each corner cut into a curve starting and ending `radius`
+// pixels (or half the edge, if that's shorter) along the edges either side.
+pub fn round(points: &[Pos], radius: f32) -> Vec<Pos> {
+    let n = points.len();
+    if radius <= 0.0 || n < 3 {
+        return points.to_vec();
+    }
+    let mut rounded = Vec::with_capacity(n * (CORNER_SEGMENTS + 1));
+    for i in 0..n {
+        let (a, p, b) = (points[(i + n - 1) % n], points[i], points[(i + 1) % n]);
+        let (to_a, to_b) = (a - p, b - p);
+        let (la, lb) = (to_a.magnitude(), to_b.magnitude());
+        if la < 1e-6 || lb < 1e-6 {
+            rounded.push(p);
+            continue;
+        }
+        let r = radius.min(la / 2.0).min(lb / 2.0);
+        let (start, end) = (p + to_a * (r / la), p + to_b * (r / lb));
+        // a quadratic Bézier with the corner as its control point
+        for j in 0..=CORNER_SEGMENTS {
+            let t = j as f32 / CORNER_SEGMENTS as f32;
+            let u = 1.0 - t;
+            rounded.push(start * (u * u) + p * (2.0 * u * t) + end * (t * t));
+        }
+    }
+    rounded
+}
+
+// Colour a graph, given each vertex's neighbours, so that no two neighbours
+// share a colour. Vertices are coloured smallest-last (always taking the one
+// with fewest neighbours left out of the rest, then colouring them in
+// reverse), which needs no more than `GRAPH_COLORS` colours for a planar
+// graph, and each keeps its `previous` colour where it's still free, so
+// colours hold still from one frame to the next.
+pub fn graph_colors(neighbors: &[Vec<usize>], previous: &[usize]) -> Vec<usize> {
+    let n = neighbors.len();
+    let mut degree: Vec<usize> = neighbors.iter().map(Vec::len).collect();
+    let mut buckets = vec![Vec::new(); degree.iter().max().map_or(1, |d| d + 1)];
+    for (v, d) in degree.iter().enumerate() {
+        buckets[*d].push(v);
+    }
+    let mut removed = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    // removing a vertex takes at most one off the least degree left
+    let mut least: usize = 0;
+    while order.len() < n {
+        let mut d = least.saturating_sub(1);
+        // buckets can still hold vertices since removed or moved down
+        let v = loop {
+            match buckets[d].pop() {
+                Some(v) if !removed[v] && degree[v] == d => break v,
+                Some(_) => {}
+                None => d += 1,
+            }
+        };
+        least = d;
+        removed[v] = true;
+        order.push(v);
+        for &u in &neighbors[v] {
+            if !removed[u] {
+                degree[u] -= 1;
+                buckets[degree[u]].push(u);
+            }
+        }
+    }
+    let mut colors = vec![usize::MAX; n];
+    for &v in order.iter().rev() {
+        let taken: Vec<usize> = neighbors[v].iter().map(|&u| colors[u]).collect();
+        let free = |c: &usize| !taken.contains(c);
+        colors[v] = match previous.get(v).filter(|c| free(c)) {
+            Some(&c) => c,
+            None => (0..).find(free).unwrap(),
+        };
+    }
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a `w` by `h` grid of vertices, each joined to the ones right, down and
+    // diagonally down-right of it: planar, with up to 6 neighbours each
+    fn triangulated_grid(w: usize, h: usize) -> Vec<Vec<usize>> {
+        let mut neighbors = vec![Vec::new(); w * h];
+        let mut join = |a: usize, b: usize| {
+            neighbors[a].push(b);
+            neighbors[b].push(a);
+        };
+        for y in 0..h {
+            for x in 0..w {
+                let v = y * w + x;
+                if x + 1 < w {
+                    join(v, v + 1);
+                }
+                if y + 1 < h {
+                    join(v, v + w);
+                }
+                if x + 1 < w && y + 1 < h {
+                    join(v, v + w + 1);
+                }
+            }
+        }
+        neighbors
+    }
+
+    // a hub joined to every vertex of a ring of `n` around it
+    fn wheel(n: usize) -> Vec<Vec<usize>> {
+        let mut neighbors = vec![(1..=n).collect::<Vec<_>>()];
+        for i in 0..n {
+            neighbors.push(vec![0, 1 + (i + n - 1) % n, 1 + (i + 1) % n]);
+        }
+        neighbors
+    }
+
+    fn assert_proper(neighbors: &[Vec<usize>], colors: &[usize]) {
+        assert_eq!(colors.len(), neighbors.len());
+        for (v, near) in neighbors.iter().enumerate() {
+            assert!(colors[v] < GRAPH_COLORS, "vertex {} got {}", v, colors[v]);
+            for &u in near {
+                assert_ne!(colors[u], colors[v], "neighbours {} and {}", u, v);
+            }
+        }
+    }
+
+    #[test]
+    fn neighbours_never_share_a_colour() {
+        for neighbors in [triangulated_grid(12, 9), wheel(7), wheel(8), Vec::new()] {
+            assert_proper(&neighbors, &graph_colors(&neighbors, &[]));
+        }
+    }
+
+    #[test]
+    fn colours_hold_still_where_they_can() {
+        let neighbors = triangulated_grid(6, 6);
+        let colors = graph_colors(&neighbors, &[]);
+        assert_eq!(graph_colors(&neighbors, &colors), colors);
+        // a clash left over from a graph that's since changed is resolved,
+        // keeping one side's colour
+        let mut clashing = colors.clone();
+        let (a, b) = (0, neighbors[0][0]);
+        clashing[a] = clashing[b];
+        let recolored = graph_colors(&neighbors, &clashing);
+        assert_proper(&neighbors, &recolored);
+        assert!(recolored[a] == clashing[a] || recolored[b] == clashing[b]);
+    }
+
+    fn square(size: f32) -> Vec<Pos> {
+        let h = size / 2.0;
+        vec![
+            Pos::new(-h, -h),
+            Pos::new(h, -h),
+            Pos::new(h, h),
+            Pos::new(-h, h),
+        ]
+    }
+
+    fn close(a: &[Pos], b: &[Pos]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (*a - *b).magnitude() < 1e-4)
+    }
+
+    #[test]
+    fn insets_pull_edges_in() {
+        assert!(close(&inset(&square(10.0), 2.0).unwrap(), &square(6.0)));
+        // the same whichever way round the outline winds
+        let clockwise: Vec<Pos> = square(10.0).into_iter().rev().collect();
+        let inset_clockwise: Vec<Pos> = square(6.0).into_iter().rev().collect();
+        assert!(close(&inset(&clockwise, 2.0).unwrap(), &inset_clockwise));
+        assert_eq!(inset(&square(10.0), 0.0), Some(square(10.0)));
+    }
+
+    #[test]
+    fn insets_drop_repeated_corners() {
+        let mut repeated = square(10.0);
+        repeated.insert(1, repeated[0]);
+        repeated.push(repeated[0]);
+        assert!(close(&inset(&repeated, 1.0).unwrap(), &square(8.0)));
+    }
+
+    #[test]
+    fn insets_can_swallow_cells() {
+        assert_eq!(inset(&square(10.0), 5.0), None);
+        assert_eq!(inset(&square(10.0), 6.0), None);
+        assert_eq!(inset(&square(10.0)[..2], 1.0), None);
+    }
+}
@@ -0,0 +1,471 @@
+// Command-line flags shared by all the visualisers, so parameter sweeps can be
+// scripted without recompiling.
+//
+// Every flag is optional; anything left out falls back to the preset file if
+// one is given, and otherwise to the sketch's own default (see each sim's
+// `Settings`).
+
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::{Args, ValueEnum};
+use serde::de::DeserializeOwned;
+
+use crate::accum::ToneMap;
+use crate::cellfill::Fill;
+use crate::clock::FixedStep;
+use crate::color::ColorMode;
+use crate::colormap::{self, ColorInput};
+use crate::field::{self, FieldMode, Fractal, NoiseKind};
+use crate::preset::{self, Error};
+use crate::target::{Assignment, TargetMode};
+use crate::{curve, flock, motion, perlin, perlin2, rng, voronoi};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Sketch {
+    Perlin,
+    Perlin2,
+    Voronoi,
+}
+
+impl fmt::Display for Sketch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Sketch::Perlin => "perlin",
+            Sketch::Perlin2 => "perlin2",
+            Sketch::Voronoi => "voronoi",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WindowSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for WindowSize {
+    type Err = String;
+
+    // "<width>x<height>", e.g. "1920x1080"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (w, h) = s
+            .split_once(['x', 'X'])
+            .ok_or_else(|| format!("expected <width>x<height>, got '{}'", s))?;
+        let dim = |d: &str| match d.trim().parse::<u32>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("'{}' isn't a positive whole number of pixels", d)),
+        };
+        Ok(WindowSize {
+            width: dim(w)?,
+            height: dim(h)?,
+        })
+    }
+}
+
+impl fmt::Display for WindowSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct SimArgs {
+    /// TOML or RON file of sketch settings, re-applied whenever it's saved
+    #[arg(long)]
+    pub preset: Option<PathBuf>,
+
+    /// Seed for the noise field and all agent randomness [default: $DUSTSTORM_SEED, or random]
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Number of agents [default: 100 for perlin and voronoi, 1000 for perlin2]
+    #[arg(long, value_parser = positive_usize, allow_negative_numbers = true)]
+    pub agents: Option<usize>,
+
+    /// Noise scale, in pixels per unit of noise space [default: 400 for perlin, 800 for perlin2]
+    #[arg(long, value_parser = positive_f64, allow_negative_numbers = true)]
+    pub noise_scale: Option<f64>,
+
+    /// Agent step size, in pixels per frame [default: 5 for perlin, 20 for perlin2, 0.3 for voronoi]
+    #[arg(long, value_parser = positive_f32, allow_negative_numbers = true)]
+    pub step_size: Option<f32>,
+
+    /// Window (or canvas) size
+    #[arg(long, default_value = "1000x1000")]
+    pub size: WindowSize,
+
+    /// Simulation steps per second, however fast frames are drawn
+    #[arg(long, default_value_t = 60.0, value_parser = positive_f32)]
+    pub sim_rate: f32,
+
+    /// Most simulation steps to catch up on in one frame before slowing down instead
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_substeps: u32,
+
+    /// How noise turns into flow: angle, or the swirling curl (perlin, perlin2) [default: angle]
+    #[arg(long, value_enum)]
+    pub field_mode: Option<FieldMode>,
+
+    /// Noise the flow field is built from (perlin, perlin2) [default: perlin]
+    #[arg(long, value_enum)]
+    pub noise: Option<NoiseKind>,
+
+    /// Octave stacking of the noise (perlin, perlin2) [default: none]
+    #[arg(long, value_enum)]
+    pub fractal: Option<Fractal>,
+
+    /// Initial targetting mode (perlin) [default: circle]
+    #[arg(long, value_enum)]
+    pub target_mode: Option<TargetMode>,
+
+    /// PNG or JPEG for the agents to paint, steering and tinting them (perlin2), or to
+    /// fill cells from like stained glass, implying --fill picture (voronoi)
+    #[arg(long)]
+    pub picture: Option<PathBuf>,
+
+    /// How cells are coloured in (voronoi) [default: none]
+    #[arg(long, value_enum)]
+    pub fill: Option<Fill>,
+
+    /// Build up a long exposure in an accumulation buffer instead of painting
+    /// straight onto the window (perlin2)
+    #[arg(long)]
+    pub accumulate: bool,
+
+    /// How the long exposure is tone mapped for display (perlin2) [default: filmic]
+    #[arg(long, value_enum)]
+    pub tone_map: Option<ToneMap>,
+
+    /// SVG file whose <path> outlines the svg target mode traces (perlin)
+    #[arg(long)]
+    pub target_svg: Option<PathBuf>,
+
+    /// Further targets, each chasing its own path (perlin), e.g. circle,noise
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub extra_targets: Option<Vec<TargetMode>>,
+
+    /// How agents are shared out between targets (perlin) [default: nearest]
+    #[arg(long, value_enum)]
+    pub assignment: Option<Assignment>,
+
+    /// Initial colour mode (perlin, perlin2), or the palette cells are filled from (voronoi)
+    /// [default: white for perlin, hue by ttl for perlin2, gradient for voronoi]
+    #[arg(long, value_enum)]
+    pub color_mode: Option<ColorMode>,
+
+    /// What places agents along the palette (perlin, perlin2) [default: z-offset]
+    #[arg(long, value_enum)]
+    pub color_input: Option<ColorInput>,
+
+    /// GIMP palette (.gpl) or JSON palette file; implies --color-mode file (all sketches)
+    #[arg(long)]
+    pub palette: Option<PathBuf>,
+}
+
+impl SimArgs {
+    // the --seed flag if given, otherwise the environment or a random seed;
+    // each sketch prints it as it starts, so a run started without one can
+    // be reproduced
+    pub fn seed(&self) -> Result<u64, Error> {
+        match self.seed {
+            Some(seed) => Ok(seed),
+            None => rng::seed_from_env(),
+        }
+    }
+
+    // a fixed-timestep clock to drive a sketch at --sim-rate
+    pub fn clock(&self) -> FixedStep {
+        FixedStep::new(self.sim_rate, self.max_substeps)
+    }
+
+    // The settings for each sketch: the preset (or defaults), overridden by
+    // any flags. Called again on every preset reload, so flags stay sticky.
+    pub fn perlin(&self) -> Result<perlin::Settings, Error> {
+        let base: perlin::Settings = self.base()?;
+        let mut settings = perlin::Settings {
+            agent_count: self.agents.unwrap_or(base.agent_count),
+            noise_scale: self.noise_scale.unwrap_or(base.noise_scale),
+            field: self.field(base.field),
+            step_size: self.step_size.unwrap_or(base.step_size),
+            target_mode: self.target_mode.unwrap_or(base.target_mode),
+            extra_targets: self.extra_targets.clone().unwrap_or(base.extra_targets),
+            assignment: self.assignment.unwrap_or(base.assignment),
+            color_mode: self.color_mode.unwrap_or(base.color_mode),
+            ..base
+        };
+        if self.palette.is_some() && self.color_mode.is_none() {
+            settings.color_mode = ColorMode::File;
+        }
+        self.colors(&mut settings.colors)?;
+        check_agent_count(settings.agent_count, 1)?;
+        check_positive(settings.noise_scale, settings.step_size)?;
+        check_field(&settings.field)?;
+        check_motion(&settings.motion)?;
+        check_curves(&settings.curves)?;
+        if let Some(file) = &self.target_svg {
+            settings.curves.svg.file = Some(file.clone());
+        }
+        settings.curves.svg.load()?;
+        check_flock(&settings.flock)?;
+        Ok(settings)
+    }
+
+    pub fn perlin2(&self) -> Result<perlin2::Settings, Error> {
+        let base: perlin2::Settings = self.base()?;
+        let mut settings = perlin2::Settings {
+            agent_count: self.agents.unwrap_or(base.agent_count),
+            noise_scale: self.noise_scale.unwrap_or(base.noise_scale),
+            field: self.field(base.field),
+            step_size: self.step_size.unwrap_or(base.step_size),
+            color_mode: self.color_mode.or(base.color_mode),
+            ..base
+        };
+        if self.palette.is_some() && self.color_mode.is_none() {
+            settings.color_mode = Some(ColorMode::File);
+        }
+        self.colors(&mut settings.colors)?;
+        check_agent_count(settings.agent_count, 1)?;
+        check_positive(settings.noise_scale, settings.step_size)?;
+        check_field(&settings.field)?;
+        check_motion(&settings.motion)?;
+        let picture = &mut settings.picture;
+        if [picture.flow, picture.speed, picture.density, picture.color]
+            .iter()
+            .any(|k| !(0.0..=1.0).contains(k))
+        {
+            return Err(Error::Invalid(
+                "picture flow, speed, density and color must be from 0 to 1".to_string(),
+            ));
+        }
+        if let Some(file) = &self.picture {
+            picture.file = Some(file.clone());
+        }
+        picture.load()?;
+        let accum = &mut settings.accum;
+        accum.enabled |= self.accumulate;
+        accum.tone_map = self.tone_map.unwrap_or(accum.tone_map);
+        if !(accum.deposit >= 0.0
+            && accum.decay >= 0.0
+            && accum.blur >= 0.0
+            && accum.exposure > 0.0)
+        {
+            return Err(Error::Invalid(
+                "accumulation deposit, decay and blur can't be negative, and exposure must be positive"
+                    .to_string(),
+            ));
+        }
+        Ok(settings)
+    }
+
+    pub fn voronoi(&self) -> Result<voronoi::Settings, Error> {
+        let base: voronoi::Settings = self.base()?;
+        let mut settings = voronoi::Settings {
+            agent_count: self.agents.unwrap_or(base.agent_count),
+            step_size: self.step_size.unwrap_or(base.step_size),
+            ..base
+        };
+        check_agent_count(settings.agent_count, voronoi::MIN_SITES)?;
+        if !(settings.step_size > 0.0 && settings.step_size.is_finite()) {
+            return Err(Error::Invalid(
+                "step size must be a positive distance".to_string(),
+            ));
+        }
+        if !(settings.repulsion_cutoff >= 0.0 && settings.repulsion_cutoff.is_finite()) {
+            return Err(Error::Invalid(
+                "repulsion cutoff must be a finite distance, or 0 for none".to_string(),
+            ));
+        }
+        let fill = &mut settings.fill;
+        fill.mode = self.fill.unwrap_or(fill.mode);
+        fill.palette = self.color_mode.unwrap_or(fill.palette);
+        if let Some(file) = &self.palette {
+            fill.colors.file = Some(file.clone());
+            if self.color_mode.is_none() {
+                fill.palette = ColorMode::File;
+            }
+        }
+        if let Some(file) = &self.picture {
+            fill.picture = Some(file.clone());
+            if self.fill.is_none() {
+                fill.mode = Fill::Picture;
+            }
+        }
+        if !(fill.area_range > 0.0 && fill.speed_range > 0.0 && fill.noise_scale > 0.0) {
+            return Err(Error::Invalid(
+                "fill area range, speed range and noise scale must be positive".to_string(),
+            ));
+        }
+        if !(fill.stroke_width >= 0.0 && fill.inset >= 0.0 && fill.rounding >= 0.0) {
+            return Err(Error::Invalid(
+                "stroke width, inset and rounding can't be negative".to_string(),
+            ));
+        }
+        fill.colors.gradient.check().map_err(Error::Invalid)?;
+        fill.load()?;
+        Ok(settings)
+    }
+
+    fn field(&self, base: field::Settings) -> field::Settings {
+        field::Settings {
+            mode: self.field_mode.unwrap_or(base.mode),
+            noise: self.noise.unwrap_or(base.noise),
+            fractal: self.fractal.unwrap_or(base.fractal),
+            ..base
+        }
+    }
+
+    // apply the colour flags to `colors`, and read its palette file
+    fn colors(&self, colors: &mut colormap::Settings) -> Result<(), Error> {
+        colors.input = self.color_input.unwrap_or(colors.input);
+        if let Some(file) = &self.palette {
+            colors.file = Some(file.clone());
+        }
+        if !(colors.speed_range > 0.0 && colors.curvature_range > 0.0) {
+            return Err(Error::Invalid(
+                "colour speed and curvature ranges must be positive".to_string(),
+            ));
+        }
+        colors.gradient.check().map_err(Error::Invalid)?;
+        colors.load()
+    }
+
+    fn base<T: DeserializeOwned + Default>(&self) -> Result<T, Error> {
+        match &self.preset {
+            Some(path) => preset::load(path),
+            None => Ok(T::default()),
+        }
+    }
+}
+
+fn check_agent_count(agent_count: usize, min_agents: usize) -> Result<(), Error> {
+    if agent_count < min_agents {
+        return Err(Error::Invalid(format!(
+            "need at least {} agents, got {}",
+            min_agents, agent_count
+        )));
+    }
+    Ok(())
+}
+
+fn check_positive(noise_scale: f64, step_size: f32) -> Result<(), Error> {
+    if !(noise_scale > 0.0 && step_size > 0.0) {
+        return Err(Error::Invalid(
+            "noise scale and step size must be positive".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn check_field(field: &field::Settings) -> Result<(), Error> {
+    if field.warp_levels > 2 {
+        return Err(Error::Invalid(format!(
+            "warp levels can be 0, 1 or 2, got {}",
+            field.warp_levels
+        )));
+    }
+    if !(field.lacunarity > 0.0 && field.warp_scale > 0.0) {
+        return Err(Error::Invalid(
+            "lacunarity and warp scale must be positive".to_string(),
+        ));
+    }
+    // no octaves would have nothing to average over
+    if field.octaves == 0 {
+        return Err(Error::Invalid(
+            "there must be at least 1 octave".to_string(),
+        ));
+    }
+    if !(field.persistence >= 0.0 && field.persistence.is_finite()) {
+        return Err(Error::Invalid(format!(
+            "persistence must be a finite number, 0 or more, got {}",
+            field.persistence
+        )));
+    }
+    Ok(())
+}
+
+fn check_curves(curves: &curve::Settings) -> Result<(), Error> {
+    let loops = [
+        (curves.circle.period, curves.circle.scale),
+        (curves.figure_eight.period, curves.figure_eight.scale),
+        (curves.lissajous.period, curves.lissajous.scale),
+        (curves.rose.period, curves.rose.scale),
+        (curves.spiral.period, curves.spiral.scale),
+        (curves.spline.period, curves.spline.scale),
+    ];
+    if loops
+        .iter()
+        .any(|&(period, scale)| !(period > 0.0 && scale.is_finite()))
+    {
+        return Err(Error::Invalid(
+            "curve periods must be positive and scales finite".to_string(),
+        ));
+    }
+    let svg = &curves.svg;
+    if !(svg.period > 0.0 && svg.margin >= 0.0 && svg.margin < 0.5) {
+        return Err(Error::Invalid(
+            "the svg trace's period must be positive, and its margin from 0 to 0.5".to_string(),
+        ));
+    }
+    if curves.rose.d == 0 {
+        return Err(Error::Invalid("rose d must be positive".to_string()));
+    }
+    if curves.spline.points.len() < 2 {
+        return Err(Error::Invalid(
+            "a spline needs at least 2 points".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn check_flock(flock: &flock::Settings) -> Result<(), Error> {
+    if !(flock.radius > 0.0 && flock.radius.is_finite()) {
+        return Err(Error::Invalid(
+            "flocking radius must be positive".to_string(),
+        ));
+    }
+    let weights = [flock.separation, flock.alignment, flock.cohesion];
+    if !weights.iter().all(|w| w.is_finite()) {
+        return Err(Error::Invalid(
+            "flocking separation, alignment and cohesion must be finite".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn check_motion(motion: &motion::Settings) -> Result<(), Error> {
+    if !(motion.mass > 0.0 && motion.drag >= 0.0 && motion.max_speed > 0.0) {
+        return Err(Error::Invalid(
+            "mass and max speed must be positive, and drag not negative".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// unwrap a result, or print the error and exit like a bad flag would
+pub fn or_exit<T>(result: Result<T, Error>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        std::process::exit(2);
+    })
+}
+
+fn positive_usize(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("'{}' isn't a positive whole number", s)),
+    }
+}
+
+pub fn positive_f64(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(n) if n > 0.0 && n.is_finite() => Ok(n),
+        _ => Err(format!("'{}' isn't a positive number", s)),
+    }
+}
+
+pub fn positive_f32(s: &str) -> Result<f32, String> {
+    positive_f64(s).map(|n| n as f32)
+}
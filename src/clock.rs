@@ -0,0 +1,129 @@
+// A fixed-timestep clock, so the simulations advance by the same steps however
+// fast frames are drawn: a 144Hz display, a 60Hz one and an offline render all
+// produce the same animation.
+
+// Real time is banked in an accumulator and spent in whole steps. If a frame
+// takes so long that more than `max_substeps` steps are owed, the excess is
+// dropped (the animation slows down rather than spiralling ever further
+// behind). What's left over, as a fraction of a step, is how far rendering
+// should interpolate between the last two states.
+pub struct FixedStep {
+    // length of a step, in seconds
+    pub dt: f32,
+    pub max_substeps: u32,
+    accumulator: f32,
+    // steps taken since the start; times are worked out from this rather than
+    // summed, so they come out exactly the same however steps are grouped
+    steps: u64,
+}
+
+impl FixedStep {
+    pub fn new(rate: f32, max_substeps: u32) -> FixedStep {
+        FixedStep {
+            dt: 1.0 / rate,
+            max_substeps,
+            accumulator: 0.0,
+            steps: 0,
+        }
+    }
+
+    // bank `elapsed` seconds of real time, and take as many steps as that
+    // pays for
+    pub fn advance(&mut self, elapsed: f32) -> Steps {
+        self.accumulator += elapsed.max(0.0);
+        // with a little slack, so frames exactly a step long reliably pay for
+        // one step despite rounding
+        let owed = (self.accumulator / self.dt + 1e-3).floor() as u32;
+        let steps = owed.min(self.max_substeps);
+        self.accumulator = (self.accumulator - steps as f32 * self.dt).max(0.0);
+        if owed > steps {
+            // give up on catching up, keeping only the partial step
+            self.accumulator %= self.dt;
+        }
+        let first = self.steps;
+        self.steps += steps as u64;
+        Steps {
+            next: first,
+            end: self.steps,
+            dt: self.dt,
+        }
+    }
+
+    // simulated seconds since the start, as of the last step taken
+    pub fn time(&self) -> f32 {
+        self.steps as f32 * self.dt
+    }
+
+    // how far real time has got between the last step and the next one, from
+    // 0 to 1
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.dt).clamp(0.0, 1.0)
+    }
+}
+
+// The steps paid for by one `advance`: the simulated time at the start of
+// each.
+pub struct Steps {
+    next: u64,
+    end: u64,
+    dt: f32,
+}
+
+impl Iterator for Steps {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.next == self.end {
+            return None;
+        }
+        let time = self.next as f32 * self.dt;
+        self.next += 1;
+        Some(time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pays_for_whole_steps() {
+        let mut clock = FixedStep::new(10.0, 8);
+        assert_eq!(clock.advance(0.35).count(), 3);
+        assert!((clock.alpha() - 0.5).abs() < 1e-3);
+        // the half step banked makes the next short frame pay for one
+        assert_eq!(clock.advance(0.06).count(), 1);
+        assert!((clock.alpha() - 0.1).abs() < 1e-3);
+        assert_eq!(clock.advance(0.0).count(), 0);
+    }
+
+    #[test]
+    fn caps_substeps() {
+        let mut clock = FixedStep::new(60.0, 4);
+        assert_eq!(clock.advance(10.0).count(), 4);
+        // the rest of the stall is dropped rather than owed, but for the
+        // partial step
+        assert!(clock.alpha() < 1.0);
+        assert!(clock.advance(1.0 / 60.0).count() <= 2);
+    }
+
+    #[test]
+    fn times_dont_depend_on_frame_rate() {
+        let times = |fps: f32, frames: usize| -> Vec<f32> {
+            let mut clock = FixedStep::new(60.0, 8);
+            (0..frames).flat_map(|_| clock.advance(1.0 / fps)).collect()
+        };
+        let slow = times(30.0, 30);
+        let fast = times(144.0, 144);
+        assert_eq!(slow.len(), 60);
+        assert_eq!(slow, fast);
+        assert!((slow[59] - 59.0 / 60.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ignores_negative_time() {
+        let mut clock = FixedStep::new(60.0, 8);
+        assert_eq!(clock.advance(-1.0).count(), 0);
+        assert_eq!(clock.alpha(), 0.0);
+    }
+}
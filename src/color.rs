@@ -0,0 +1,65 @@
+// Colours, and the colour modes used to tint agents; see `colormap` for the
+// palettes behind them.
+
+use palette::convert::FromColorUnclamped;
+use palette::{Hsv, Srgb, Srgba};
+use serde::{Deserialize, Serialize};
+
+// non-linear sRGB with alpha, all components in 0..1
+pub type Color = Srgba;
+
+pub const BLACK: Color = Color::new(0.0, 0.0, 0.0, 1.0);
+pub const WHITE: Color = Color::new(1.0, 1.0, 1.0, 1.0);
+pub const RED: Color = Color::new(1.0, 0.0, 0.0, 1.0);
+
+// hue in turns (0..1, wrapping) like nannou's `hsv`, rather than degrees
+pub fn hsv(hue: f32, saturation: f32, value: f32) -> Color {
+    let hsv = Hsv::new_srgb(hue * 360.0, saturation, value);
+    Srgb::from_color_unclamped(hsv).into()
+}
+
+pub fn rgb8(r: u8, g: u8, b: u8) -> Color {
+    Srgb::new(r, g, b).into_format::<f32>().into()
+}
+
+// from red to blue as `t` goes from 0 to 1
+pub fn red_blue(t: f32) -> Color {
+    rgb8(15 + (t * 240.0) as u8, 0, 255 - (t * 240.0) as u8)
+}
+
+// most of the way round the hue wheel from yellow, saturating as `t` goes
+// from 0 to 1
+pub fn hue_rotate(t: f32) -> Color {
+    let hsv = Hsv::new_srgb(50.0 + t * 300.0, 0.5 + t * 0.5, 1.0);
+    Srgb::from_color_unclamped(hsv).into()
+}
+
+// How agents are coloured; each but white maps a position from 0 to 1, picked
+// as set in `colormap::Settings`, to a colour.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorMode {
+    White,
+    RedBlue,
+    HueRotate,
+    // the multi-stop gradient
+    Gradient,
+    // the cosine palette
+    Cosine,
+    // the palette file
+    File,
+}
+
+impl ColorMode {
+    // the next mode in the C-key cycle
+    pub fn next(self) -> ColorMode {
+        match self {
+            ColorMode::White => ColorMode::RedBlue,
+            ColorMode::RedBlue => ColorMode::HueRotate,
+            ColorMode::HueRotate => ColorMode::Gradient,
+            ColorMode::Gradient => ColorMode::Cosine,
+            ColorMode::Cosine => ColorMode::File,
+            ColorMode::File => ColorMode::White,
+        }
+    }
+}
@@ -0,0 +1,571 @@
+// Colour maps: the palettes agents can be coloured from, and what about an
+// agent picks where along the palette it falls. Palettes are multi-stop
+// gradients blended in a perceptual space, cosine palettes
+// (a + b·cos(2π(c·t + d)), per channel) or gradients read from palette files.
+
+use std::f32::consts::PI;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use nannou::geom::Rect;
+use palette::{FromColor, Lch, Mix, Oklab, Oklch, Srgb};
+use serde::{Deserialize, Serialize};
+
+use crate::agent::{Agent, MAX_TTL};
+use crate::color::{self, Color, ColorMode, WHITE};
+use crate::pos::Pos;
+use crate::preset::Error;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorInput {
+    // each agent's fixed random offset
+    ZOffset,
+    // seconds left to live, out of the most an agent starts with
+    Ttl,
+    // how far through its life an agent is; along perlin's trails, how old
+    // each point is instead
+    Age,
+    // distance to the agent's target, out to half the window's shorter side
+    Distance,
+    // how far the last step went, from standing still to `speed_range`
+    // times the agent's step size
+    Speed,
+    // which way the last step went, once round the palette anticlockwise
+    // from the right, so cyclic palettes suit it best
+    Heading,
+    // how sharply the path bends: the middle of the palette for straight
+    // on, one end for left turns and the other for right
+    Curvature,
+}
+
+impl ColorInput {
+    // the next input in the G-key cycle
+    pub fn next(self) -> ColorInput {
+        match self {
+            ColorInput::ZOffset => ColorInput::Ttl,
+            ColorInput::Ttl => ColorInput::Age,
+            ColorInput::Age => ColorInput::Distance,
+            ColorInput::Distance => ColorInput::Speed,
+            ColorInput::Speed => ColorInput::Heading,
+            ColorInput::Heading => ColorInput::Curvature,
+            ColorInput::Curvature => ColorInput::ZOffset,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    // what places an agent along the palette
+    pub input: ColorInput,
+    // the speed, in step sizes per step, and the curvature (one over the
+    // radius of the turn, in pixels) that reach the ends of the palette
+    pub speed_range: f32,
+    pub curvature_range: f32,
+    // the palettes for the gradient and cosine colour modes
+    pub gradient: Gradient,
+    pub cosine: Cosine,
+    // a GIMP palette (.gpl) or JSON file for the file colour mode: a list
+    // of hex colours spaced evenly, or a gradient written out like `gradient`
+    pub file: Option<PathBuf>,
+    // the gradient read from `file` by `load`
+    #[serde(skip)]
+    loaded: Option<Gradient>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            input: ColorInput::ZOffset,
+            speed_range: 2.0,
+            curvature_range: 0.05,
+            gradient: Gradient::default(),
+            cosine: Cosine::default(),
+            file: None,
+            loaded: None,
+        }
+    }
+}
+
+impl Settings {
+    // read the palette in `file`, if there is one
+    pub fn load(&mut self) -> Result<(), Error> {
+        self.loaded = match &self.file {
+            Some(path) => Some(read_palette(path, self.gradient.space)?),
+            None => None,
+        };
+        Ok(())
+    }
+
+    // the colour `t` (0..1) of the way along the palette for `mode`; white
+    // for the file mode without a file
+    pub fn color(&self, mode: ColorMode, t: f32) -> Color {
+        match mode {
+            ColorMode::White => WHITE,
+            ColorMode::RedBlue => color::red_blue(t),
+            ColorMode::HueRotate => color::hue_rotate(t),
+            ColorMode::Gradient => self.gradient.sample(t),
+            ColorMode::Cosine => self.cosine.sample(t),
+            ColorMode::File => self.loaded.as_ref().map_or(WHITE, |g| g.sample(t)),
+        }
+    }
+
+    // where `agent` falls along the palette, from 0 to 1, given the age to
+    // use for it (also 0 to 1) and where its target is in a window `win`
+    pub fn position(&self, agent: &Agent, age: f32, target: Pos, win: Rect) -> f32 {
+        let t = match self.input {
+            ColorInput::ZOffset => agent.z_offset,
+            ColorInput::Ttl => agent.ttl / MAX_TTL,
+            ColorInput::Age => age,
+            ColorInput::Distance => {
+                (agent.pos - target).magnitude() / (win.w().min(win.h()) / 2.0).max(1.0)
+            }
+            ColorInput::Speed | ColorInput::Heading | ColorInput::Curvature => {
+                let steps = (agent.last_step(), agent.prev_step);
+                return self.path_position(steps, agent.step_size).unwrap_or(0.5);
+            }
+        };
+        t.clamp(0.0, 1.0)
+    }
+
+    // For the inputs that come from the shape of a path, where a point on it
+    // falls along the palette given the `step` that reached it and the one
+    // `before` that, from an agent whose step size is `step_size`; nothing for
+    // the other inputs.
+    pub fn path_position(&self, (step, before): (Pos, Pos), step_size: f32) -> Option<f32> {
+        let t = match self.input {
+            ColorInput::Speed => step.magnitude() / (step_size * self.speed_range).max(1e-6),
+            ColorInput::Heading => (step.y.atan2(step.x) / (2.0 * PI)).rem_euclid(1.0),
+            ColorInput::Curvature => {
+                let length = step.magnitude();
+                match length > 1e-6 && before.magnitude() > 1e-6 {
+                    true => {
+                        // the signed angle turned between the steps, per
+                        // pixel travelled
+                        let cross = before.x * step.y - before.y * step.x;
+                        let dot = before.x * step.x + before.y * step.y;
+                        let curvature = cross.atan2(dot) / length;
+                        0.5 + 0.5 * curvature / self.curvature_range
+                    }
+                    false => 0.5,
+                }
+            }
+            _ => return None,
+        };
+        Some(t.clamp(0.0, 1.0))
+    }
+}
+
+// the colour spaces gradients can be blended in
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Space {
+    // perceptually even lightness and hue, so blends don't go muddy
+    Oklab,
+    // Oklab's lightness, chroma and hue, blending round the hue wheel
+    // rather than straight across it
+    Oklch,
+    // CIE L*C*h°, likewise round the hue wheel
+    Lch,
+    // straight between the sRGB values
+    Srgb,
+}
+
+// a colour written as "#rrggbb" (or "#rgb")
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Hex(pub Srgb<u8>);
+
+impl TryFrom<String> for Hex {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+            .map(Hex)
+            .map_err(|e| format!("'{}' isn't a hex colour: {}", text, e))
+    }
+}
+
+impl Hex {
+    pub fn color(self) -> Color {
+        self.0.into_format::<f32>().into()
+    }
+}
+
+impl From<Hex> for String {
+    fn from(hex: Hex) -> String {
+        let Hex(c) = hex;
+        format!("#{:02x}{:02x}{:02x}", c.red, c.green, c.blue)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stop {
+    // where along the gradient, from 0 to 1
+    pub at: f32,
+    pub color: Hex,
+}
+
+// Colours at stops along the way from 0 to 1, blended in `space` between
+// them, and held past the first and last.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Gradient {
+    pub space: Space,
+    // in order of `at`
+    pub stops: Vec<Stop>,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        // deep blue through magenta and orange to pale yellow
+        let stops = ["#1b1f5e", "#8a2a8a", "#f0643c", "#f8e45c"]
+            .iter()
+            .enumerate()
+            .map(|(i, hex)| Stop {
+                at: i as f32 / 3.0,
+                color: Hex(hex.parse().unwrap()),
+            })
+            .collect();
+        Gradient {
+            space: Space::Oklab,
+            stops,
+        }
+    }
+}
+
+impl Gradient {
+    // `colors` spaced evenly from 0 to 1
+    pub fn even(space: Space, colors: Vec<Hex>) -> Gradient {
+        let last = (colors.len() as f32 - 1.0).max(1.0);
+        let stops = colors
+            .into_iter()
+            .enumerate()
+            .map(|(i, color)| Stop {
+                at: i as f32 / last,
+                color,
+            })
+            .collect();
+        Gradient { space, stops }
+    }
+
+    // why the stops can't make a gradient, if they can't
+    pub fn check(&self) -> Result<(), String> {
+        if self.stops.is_empty() {
+            return Err("a gradient needs at least one stop".to_string());
+        }
+        if self.stops.iter().any(|s| !s.at.is_finite())
+            || self.stops.windows(2).any(|w| w[0].at > w[1].at)
+        {
+            return Err("gradient stops must be finite and in order".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn sample(&self, t: f32) -> Color {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return WHITE,
+        };
+        let next = match self.stops.iter().position(|s| s.at >= t) {
+            Some(0) => return first.color.color(),
+            Some(next) if self.stops[next].at == t => return self.stops[next].color.color(),
+            Some(next) => next,
+            None => return last.color.color(),
+        };
+        let (a, b) = (&self.stops[next - 1], &self.stops[next]);
+        let span = b.at - a.at;
+        let k = match span > 0.0 {
+            true => (t - a.at) / span,
+            false => 1.0,
+        };
+        let (a, b) = (
+            a.color.0.into_format::<f32>(),
+            b.color.0.into_format::<f32>(),
+        );
+        let mixed: Srgb = match self.space {
+            Space::Oklab => mix::<Oklab>(a, b, k),
+            Space::Oklch => mix::<Oklch>(a, b, k),
+            Space::Lch => mix::<Lch>(a, b, k),
+            Space::Srgb => a.mix(b, k),
+        };
+        mixed.into()
+    }
+}
+
+// `a` and `b` blended `k` of the way in the colour space `S`, back in sRGB
+// and clamped into its gamut
+fn mix<S>(a: Srgb, b: Srgb, k: f32) -> Srgb
+where
+    S: FromColor<Srgb> + Mix<Scalar = f32>,
+    Srgb: FromColor<S>,
+{
+    Srgb::from_color(S::from_color(a).mix(S::from_color(b), k))
+}
+
+// Iñigo Quilez's cosine palettes: each channel is a + b·cos(2π(c·t + d)).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Cosine {
+    // the middle of each channel, how far it swings, how many times it
+    // swings from 0 to 1, and where in its swing it starts (in turns)
+    pub a: [f32; 3],
+    pub b: [f32; 3],
+    pub c: [f32; 3],
+    pub d: [f32; 3],
+}
+
+impl Default for Cosine {
+    fn default() -> Self {
+        // a rainbow
+        Cosine {
+            a: [0.5; 3],
+            b: [0.5; 3],
+            c: [1.0; 3],
+            d: [0.0, 0.33, 0.67],
+        }
+    }
+}
+
+impl Cosine {
+    pub fn sample(&self, t: f32) -> Color {
+        let channel =
+            |i: usize| self.a[i] + self.b[i] * (2.0 * PI * (self.c[i] * t + self.d[i])).cos();
+        let [r, g, b] = [0, 1, 2].map(|i| channel(i).clamp(0.0, 1.0));
+        Color::new(r, g, b, 1.0)
+    }
+}
+
+// a palette file as a gradient, blending evenly spaced colours in `space`
+fn read_palette(path: &Path, space: Space) -> Result<Gradient, Error> {
+    let text = fs::read_to_string(path).map_err(|e| Error::Io(path.to_path_buf(), e))?;
+    let invalid = |e: String| Error::Invalid(format!("couldn't read {}: {}", path.display(), e));
+    let gradient = match path.extension().and_then(|e| e.to_str()) {
+        Some("gpl") => Gradient::even(space, parse_gpl(&text).map_err(invalid)?),
+        // either a list of colours or a whole gradient
+        Some("json") => {
+            let json: serde_json::Value =
+                serde_json::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+            let gradient = match json.is_array() {
+                true => serde_json::from_value(json).map(|colors| Gradient::even(space, colors)),
+                false => serde_json::from_value(json),
+            };
+            gradient.map_err(|e| invalid(e.to_string()))?
+        }
+        _ => return Err(invalid("palettes must be .gpl or .json".to_string())),
+    };
+    gradient.check().map_err(invalid)?;
+    Ok(gradient)
+}
+
+// the colours in a GIMP palette: a "GIMP Palette" line, then "r g b name"
+// lines, among comments and Name and Columns headers
+fn parse_gpl(text: &str) -> Result<Vec<Hex>, String> {
+    let mut lines = text.lines();
+    if lines.next().map(str::trim) != Some("GIMP Palette") {
+        return Err("not a GIMP palette".to_string());
+    }
+    let mut colors = Vec::new();
+    for line in lines.map(str::trim) {
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("Name:")
+            || line.starts_with("Columns:")
+        {
+            continue;
+        }
+        let channels: Vec<u8> = line
+            .split_whitespace()
+            .take(3)
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("expected a colour, got '{}'", line))?;
+        match channels[..] {
+            [r, g, b] => colors.push(Hex(Srgb::new(r, g, b))),
+            _ => return Err(format!("expected a colour, got '{}'", line)),
+        }
+    }
+    Ok(colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(text: &str) -> Hex {
+        Hex::try_from(text.to_string()).unwrap()
+    }
+
+    fn rgb(color: Color) -> [u8; 3] {
+        let c: Srgb<u8> = color.color.into_format();
+        [c.red, c.green, c.blue]
+    }
+
+    // `text` written to a palette file named `name`, read back in Oklab
+    fn read(name: &str, text: &str) -> Result<Gradient, Error> {
+        let path = std::env::temp_dir().join(format!("duststorm-{}-{}", std::process::id(), name));
+        fs::write(&path, text).unwrap();
+        let gradient = read_palette(&path, Space::Oklab);
+        fs::remove_file(&path).unwrap();
+        gradient
+    }
+
+    #[test]
+    fn hex_colours_long_and_short() {
+        assert_eq!(hex("#ff8800"), Hex(Srgb::new(255, 136, 0)));
+        assert_eq!(hex("#f80"), Hex(Srgb::new(255, 136, 0)));
+        assert_eq!(hex("#1B1f5E"), Hex(Srgb::new(27, 31, 94)));
+        assert_eq!(String::from(hex("#f80")), "#ff8800");
+        for bad in ["#ff88", "#ggg", "#ff88001", "", "red"] {
+            assert!(Hex::try_from(bad.to_string()).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn gradients_hit_their_stops_in_every_space() {
+        for space in [Space::Oklab, Space::Oklch, Space::Lch, Space::Srgb] {
+            let gradient =
+                Gradient::even(space, vec![hex("#1b1f5e"), hex("#f0643c"), hex("#ffffff")]);
+            assert_eq!(gradient.check(), Ok(()));
+            assert_eq!(rgb(gradient.sample(0.0)), [27, 31, 94], "{:?}", space);
+            assert_eq!(rgb(gradient.sample(0.5)), [240, 100, 60], "{:?}", space);
+            assert_eq!(rgb(gradient.sample(1.0)), [255, 255, 255], "{:?}", space);
+            // held past the ends
+            assert_eq!(gradient.sample(-1.0), gradient.sample(0.0));
+            assert_eq!(gradient.sample(2.0), gradient.sample(1.0));
+        }
+        // sRGB blends straight between the values, Oklab halfway in
+        // perceived lightness
+        let black_white = |space| Gradient::even(space, vec![hex("#000"), hex("#fff")]);
+        assert_eq!(rgb(black_white(Space::Srgb).sample(0.5)), [128, 128, 128]);
+        let middle = Oklab::from_color(black_white(Space::Oklab).sample(0.5).color);
+        assert!((middle.l - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn single_stops_and_stops_out_of_order() {
+        let single = Gradient::even(Space::Oklab, vec![hex("#f0643c")]);
+        assert_eq!(single.check(), Ok(()));
+        for t in [0.0, 0.3, 1.0] {
+            assert_eq!(rgb(single.sample(t)), [240, 100, 60]);
+        }
+        let mut unsorted = Gradient::default();
+        unsorted.stops.swap(0, 2);
+        assert!(unsorted.check().is_err());
+        let mut nan = Gradient::default();
+        nan.stops[1].at = f32::NAN;
+        assert!(nan.check().is_err());
+        assert!(Gradient::even(Space::Oklab, Vec::new()).check().is_err());
+    }
+
+    #[test]
+    fn reads_palette_files() {
+        let gpl =
+            "GIMP Palette\nName: Test\nColumns: 2\n# a comment\n\n255 0 0 Red\n0 0 255\tBlue\n";
+        let gradient = read("ok.gpl", gpl).unwrap();
+        assert_eq!(
+            gradient,
+            Gradient::even(Space::Oklab, vec![hex("#f00"), hex("#00f")])
+        );
+        let list = read("list.json", r##"["#ff0000", "#00f"]"##).unwrap();
+        assert_eq!(list, gradient);
+        let whole = r##"{"space": "srgb", "stops": [{"at": 0.0, "color": "#000"}, {"at": 0.2, "color": "#fff"}]}"##;
+        let whole = read("whole.json", whole).unwrap();
+        assert_eq!(whole.space, Space::Srgb);
+        assert_eq!(whole.stops[1].at, 0.2);
+    }
+
+    #[test]
+    fn malformed_palette_files_are_errors() {
+        let bad = [
+            ("header.gpl", "Not A Palette\n255 0 0\n"),
+            ("channel.gpl", "GIMP Palette\n255 0 red\n"),
+            ("short.gpl", "GIMP Palette\n255 0\n"),
+            ("range.gpl", "GIMP Palette\n256 0 0\n"),
+            ("empty.gpl", "GIMP Palette\n# nothing\n"),
+            ("syntax.json", "[\"#ff0000\""),
+            ("colour.json", "[\"#ff00\"]"),
+            (
+                "unsorted.json",
+                r##"{"stops": [{"at": 0.5, "color": "#000"}, {"at": 0.1, "color": "#fff"}]}"##,
+            ),
+            ("unknown.json", r##"{"stops": [], "extra": 1}"##),
+            ("palette.txt", "#ff0000\n"),
+        ];
+        for (name, text) in bad {
+            assert!(
+                matches!(read(name, text), Err(Error::Invalid(_))),
+                "{}",
+                name
+            );
+        }
+        let missing = Path::new("/nonexistent/duststorm.gpl");
+        assert!(matches!(
+            read_palette(missing, Space::Oklab),
+            Err(Error::Io(..))
+        ));
+    }
+
+    // where an agent with a step size of 10 falls along the palette for
+    // `input`, having taken `before` and then `step`
+    fn position(input: ColorInput, before: Pos, step: Pos) -> f32 {
+        let settings = Settings {
+            input,
+            ..Default::default()
+        };
+        let win = Rect::from_w_h(400.0, 300.0);
+        let motion = crate::motion::Settings::default();
+        let mut agent = Agent::new(win, true, 10.0, motion, &mut crate::rng::from_seed(1));
+        agent.prev_pos = Pos::new(20.0, -5.0);
+        agent.pos = agent.prev_pos + step;
+        agent.prev_step = before;
+        let t = settings.position(&agent, 0.0, Pos::new(0.0, 0.0), win);
+        assert!((0.0..=1.0).contains(&t), "{:?} gave {}", input, t);
+        t
+    }
+
+    #[test]
+    fn speed_runs_from_still_to_the_range() {
+        let p = |x, y| position(ColorInput::Speed, Pos::new(10.0, 0.0), Pos::new(x, y));
+        assert_eq!(p(0.0, 0.0), 0.0);
+        assert!((p(10.0, 0.0) - 0.5).abs() < 1e-6);
+        // twice the step size is the default range
+        assert_eq!(p(0.0, -20.0), 1.0);
+        assert_eq!(p(300.0, 400.0), 1.0);
+    }
+
+    #[test]
+    fn heading_goes_round_once() {
+        let p = |x, y| position(ColorInput::Heading, Pos::new(0.0, 0.0), Pos::new(x, y));
+        assert_eq!(p(0.0, 0.0), 0.0);
+        assert_eq!(p(5.0, 0.0), 0.0);
+        assert!((p(0.0, 5.0) - 0.25).abs() < 1e-6);
+        assert!((p(-5.0, 0.0) - 0.5).abs() < 1e-6);
+        assert!((p(0.0, -5.0) - 0.75).abs() < 1e-6);
+        // just short of a full turn
+        assert!(p(5.0, -1e-3) > 0.99);
+    }
+
+    #[test]
+    fn curvature_is_centred_on_straight_lines() {
+        let p = |before: Pos, step: Pos| position(ColorInput::Curvature, before, step);
+        let (right, up) = (Pos::new(5.0, 0.0), Pos::new(0.0, 5.0));
+        let zero = Pos::new(0.0, 0.0);
+        // straight on, an infinite radius, or no step to turn from or by
+        assert_eq!(p(right, right * 2.0), 0.5);
+        assert_eq!(p(zero, right), 0.5);
+        assert_eq!(p(right, zero), 0.5);
+        assert_eq!(p(zero, zero), 0.5);
+        assert_eq!(p(right, Pos::new(1e-7, 0.0)), 0.5);
+        // a gentle left turn, and the same to the right
+        let left = p(right, Pos::new(5.0, 0.05));
+        assert!(left > 0.5 && left < 1.0);
+        assert!((p(right, Pos::new(5.0, -0.05)) - (1.0 - left)).abs() < 1e-5);
+        // sharp turns past the range, and doubling back, are pinned to the ends
+        assert_eq!(p(right, up), 1.0);
+        assert_eq!(p(right, up * -1.0), 0.0);
+        assert_eq!(p(right, right * -1.0), 1.0);
+    }
+}
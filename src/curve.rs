@@ -0,0 +1,536 @@
+// Parametric curves for targets to follow round and round: circles, figure
+// eights, Lissajous figures, roses, Archimedean spirals and Catmull-Rom
+// splines, plus outlines traced from SVG files. Each loops once every
+// `period` seconds, and the parametric ones are `scale` pixels from the
+// canvas center at their widest.
+
+use std::f32::consts::PI;
+use std::fs;
+use std::path::PathBuf;
+
+use nannou::geom::Rect;
+use serde::{Deserialize, Serialize};
+
+use crate::pos::Pos;
+use crate::preset::Error;
+use crate::svgpath;
+
+// points an SVG outline is resampled into
+const TRACE_POINTS: usize = 2048;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub circle: Circle,
+    pub figure_eight: FigureEight,
+    pub lissajous: Lissajous,
+    pub rose: Rose,
+    pub spiral: Spiral,
+    pub spline: Spline,
+    pub svg: SvgTrace,
+}
+
+// clockwise round the canvas center, `scale` being the radius
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Circle {
+    pub period: f32,
+    pub scale: f32,
+}
+
+impl Default for Circle {
+    fn default() -> Self {
+        Circle {
+            period: 10.0,
+            scale: 300.0,
+        }
+    }
+}
+
+impl Circle {
+    pub fn at(&self, time: f32, phase: f32) -> Pos {
+        let theta = -2.0 * PI * turns(time, self.period, phase);
+        Pos::new(theta.cos(), theta.sin()) * self.scale
+    }
+}
+
+// a vertical figure eight, twice as tall as wide
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FigureEight {
+    pub period: f32,
+    pub scale: f32,
+}
+
+impl Default for FigureEight {
+    fn default() -> Self {
+        FigureEight {
+            period: 10.0,
+            scale: 300.0,
+        }
+    }
+}
+
+impl FigureEight {
+    pub fn at(&self, time: f32, phase: f32) -> Pos {
+        let theta = -2.0 * PI * turns(time, self.period, phase);
+        Pos::new(-(theta * 2.0).sin() / 2.0, theta.sin()) * self.scale
+    }
+}
+
+// x = sin(aθ + δ), y = sin(bθ)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Lissajous {
+    pub period: f32,
+    pub scale: f32,
+    pub a: f32,
+    pub b: f32,
+    // in radians
+    pub delta: f32,
+}
+
+impl Default for Lissajous {
+    fn default() -> Self {
+        Lissajous {
+            period: 20.0,
+            scale: 300.0,
+            a: 3.0,
+            b: 2.0,
+            delta: PI / 2.0,
+        }
+    }
+}
+
+impl Lissajous {
+    // `phase` is how far along the loop to start, as a fraction of it
+    pub fn at(&self, time: f32, phase: f32) -> Pos {
+        let theta = 2.0 * PI * turns(time, self.period, phase);
+        Pos::new((self.a * theta + self.delta).sin(), (self.b * theta).sin()) * self.scale
+    }
+}
+
+// r = cos(n/d θ): for n and d with no common factor, n petals if both are
+// odd and 2n otherwise
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rose {
+    pub period: f32,
+    pub scale: f32,
+    pub n: u32,
+    pub d: u32,
+}
+
+impl Default for Rose {
+    fn default() -> Self {
+        Rose {
+            period: 20.0,
+            scale: 300.0,
+            n: 5,
+            d: 1,
+        }
+    }
+}
+
+impl Rose {
+    pub fn at(&self, time: f32, phase: f32) -> Pos {
+        // the whole curve takes d full turns to close
+        let theta = 2.0 * PI * self.d as f32 * turns(time, self.period, phase);
+        let r = (self.n as f32 / self.d as f32 * theta).cos();
+        Pos::new(theta.cos(), theta.sin()) * r * self.scale
+    }
+}
+
+// r = θ, winding out from the center over `turns` turns then back in again
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Spiral {
+    pub period: f32,
+    pub scale: f32,
+    pub turns: f32,
+}
+
+impl Default for Spiral {
+    fn default() -> Self {
+        Spiral {
+            period: 20.0,
+            scale: 300.0,
+            turns: 3.0,
+        }
+    }
+}
+
+impl Spiral {
+    pub fn at(&self, time: f32, phase: f32) -> Pos {
+        // out for the first half of the loop, and back along the same arm for
+        // the second, so there's no jump back to the middle
+        let out = 1.0 - (2.0 * turns(time, self.period, phase) - 1.0).abs();
+        let theta = 2.0 * PI * self.turns * out;
+        Pos::new(theta.cos(), theta.sin()) * out * self.scale
+    }
+}
+
+// A closed Catmull-Rom spline through control points, given as fractions of
+// `scale` from the canvas center. Each span between points takes the same
+// time, however long it is.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Spline {
+    pub period: f32,
+    pub scale: f32,
+    pub points: Vec<[f32; 2]>,
+}
+
+impl Default for Spline {
+    fn default() -> Self {
+        // a five pointed star
+        let points = (0..10)
+            .map(|i| {
+                let theta = PI / 2.0 + i as f32 * PI / 5.0;
+                let r = if i % 2 == 0 { 1.0 } else { 0.4 };
+                [r * theta.cos(), r * theta.sin()]
+            })
+            .collect();
+        Spline {
+            period: 20.0,
+            scale: 300.0,
+            points,
+        }
+    }
+}
+
+impl Spline {
+    pub fn at(&self, time: f32, phase: f32) -> Pos {
+        let n = self.points.len();
+        if n == 0 {
+            return Pos::new(0.0, 0.0);
+        }
+        let along = turns(time, self.period, phase) * n as f32;
+        let span = (along as usize).min(n - 1);
+        let t = along - span as f32;
+        let point = |i: usize| {
+            let [x, y] = self.points[(span + i + n - 1) % n];
+            Pos::new(x, y)
+        };
+        catmull_rom([point(0), point(1), point(2), point(3)], t) * self.scale
+    }
+}
+
+// The `<path>` outlines of an SVG file, fit to the window. The target moves
+// along each subpath in turn at a steady speed, however the outline's curves
+// and corners are spaced out, and jumps straight from the end of one to the
+// start of the next, like a pen lifted off the paper.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SvgTrace {
+    pub period: f32,
+    pub file: Option<PathBuf>,
+    // fraction of the window's width and height left clear on each side
+    pub margin: f32,
+    // each subpath of the outline, evenly spaced along its length (the same
+    // spacing for all of them), y up and centred on the origin; filled in
+    // from `file` by `load`
+    #[serde(skip)]
+    subpaths: Vec<Vec<Pos>>,
+    // the width and height of the outline
+    #[serde(skip)]
+    size: Pos,
+}
+
+impl Default for SvgTrace {
+    fn default() -> Self {
+        SvgTrace {
+            period: 20.0,
+            file: None,
+            margin: 0.1,
+            subpaths: Vec::new(),
+            size: Pos::new(0.0, 0.0),
+        }
+    }
+}
+
+impl SvgTrace {
+    // read the outline from `file`, if there is one
+    pub fn load(&mut self) -> Result<(), Error> {
+        let Some(path) = self.file.clone() else {
+            return Ok(());
+        };
+        let text = fs::read_to_string(&path).map_err(|e| Error::Io(path.clone(), e))?;
+        let invalid = |e| Error::Invalid(format!("couldn't trace {}: {}", path.display(), e));
+        let subpaths = svgpath::parse_svg(&text).map_err(invalid)?;
+        self.set_outline(subpaths).map_err(invalid)
+    }
+
+    // trace `subpaths`, in SVG's y-down coordinates
+    fn set_outline(&mut self, subpaths: Vec<Vec<Pos>>) -> Result<(), String> {
+        // y up, leaving out any with nothing to trace
+        let subpaths: Vec<Vec<Pos>> = subpaths
+            .into_iter()
+            .map(|line| line.into_iter().map(|p| Pos::new(p.x, -p.y)).collect())
+            .filter(|line: &Vec<Pos>| length(line) > 0.0)
+            .collect();
+        if subpaths.is_empty() {
+            return Err("its outlines have no length".to_string());
+        }
+        let first = subpaths[0][0];
+        let (min, max) = subpaths
+            .iter()
+            .flatten()
+            .fold((first, first), |(min, max), p| {
+                (
+                    Pos::new(min.x.min(p.x), min.y.min(p.y)),
+                    Pos::new(max.x.max(p.x), max.y.max(p.y)),
+                )
+            });
+        let centre = (min + max) * 0.5;
+        // only the subpaths themselves count towards the length traced, not
+        // the jumps between them
+        let spacing = subpaths.iter().map(|line| length(line)).sum::<f32>() / TRACE_POINTS as f32;
+        self.subpaths = subpaths
+            .iter()
+            .map(|line| {
+                let count = ((length(line) / spacing).round() as usize).max(1);
+                let mut points = resample(line, count);
+                points.push(line[line.len() - 1]);
+                points.into_iter().map(|p| p - centre).collect()
+            })
+            .collect();
+        self.size = max - min;
+        Ok(())
+    }
+
+    // the point along the outline, scaled to fit `win`; the middle of the
+    // window if there's no outline
+    pub fn at(&self, time: f32, phase: f32, win: Rect) -> Pos {
+        let middle = Pos::new(win.x(), win.y());
+        // the spans between points, of which each subpath has one fewer than
+        // it has points
+        let spans: usize = self.subpaths.iter().map(|line| line.len() - 1).sum();
+        if spans == 0 {
+            return middle;
+        }
+        let mut along = turns(time, self.period, phase) * spans as f32;
+        // the subpath it's on, and how far along that
+        let mut rest = &self.subpaths[..];
+        while rest.len() > 1 && along >= (rest[0].len() - 1) as f32 {
+            along -= (rest[0].len() - 1) as f32;
+            rest = &rest[1..];
+        }
+        let line = &rest[0];
+        let i = (along as usize).min(line.len() - 2);
+        let point = line[i] + (line[i + 1] - line[i]) * (along - i as f32).min(1.0);
+        let room = 1.0 - 2.0 * self.margin;
+        let scale =
+            (win.w() * room / self.size.x.max(1e-6)).min(win.h() * room / self.size.y.max(1e-6));
+        middle + point * scale
+    }
+}
+
+// the length of a polyline
+fn length(line: &[Pos]) -> f32 {
+    line.windows(2).map(|w| (w[1] - w[0]).magnitude()).sum()
+}
+
+// `count` points spaced evenly along a polyline, from its start up to (but
+// not including) its end
+fn resample(line: &[Pos], count: usize) -> Vec<Pos> {
+    let lengths: Vec<f32> = line.windows(2).map(|w| (w[1] - w[0]).magnitude()).collect();
+    let total: f32 = lengths.iter().sum();
+    if total == 0.0 {
+        return vec![line[0]];
+    }
+    let mut points = Vec::with_capacity(count);
+    // the segment the next point falls in, and how far along the line it
+    // starts
+    let (mut segment, mut start) = (0, 0.0);
+    for i in 0..count {
+        let distance = total * i as f32 / count as f32;
+        while segment < lengths.len() - 1 && start + lengths[segment] < distance {
+            start += lengths[segment];
+            segment += 1;
+        }
+        let length = lengths[segment];
+        let t = match length > 0.0 {
+            true => ((distance - start) / length).min(1.0),
+            false => 0.0,
+        };
+        points.push(line[segment] + (line[segment + 1] - line[segment]) * t);
+    }
+    points
+}
+
+// how far round its loop a curve is, from 0 to 1
+fn turns(time: f32, period: f32, phase: f32) -> f32 {
+    (time / period + phase).rem_euclid(1.0)
+}
+
+// the point `t` of the way from p[1] to p[2] on a uniform Catmull-Rom spline
+fn catmull_rom(p: [Pos; 4], t: f32) -> Pos {
+    let (t2, t3) = (t * t, t * t * t);
+    (p[1] * 2.0
+        + (p[2] - p[0]) * t
+        + (p[0] * 2.0 - p[1] * 5.0 + p[2] * 4.0 - p[3]) * t2
+        + (p[1] * 3.0 - p[0] - p[2] * 3.0 + p[3]) * t3)
+        * 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 10 pixel square at the origin and a 10 pixel line 100 pixels to its
+    // right, traced in a window they just fit across
+    fn square_and_line() -> (SvgTrace, Rect) {
+        let p = |x, y| Pos::new(x, y);
+        let square = vec![
+            p(0.0, 0.0),
+            p(10.0, 0.0),
+            p(10.0, 10.0),
+            p(0.0, 10.0),
+            p(0.0, 0.0),
+        ];
+        let line = vec![p(100.0, 0.0), p(110.0, 0.0)];
+        let mut trace = SvgTrace {
+            margin: 0.0,
+            ..Default::default()
+        };
+        trace.set_outline(vec![square, line]).unwrap();
+        (trace, Rect::from_w_h(110.0, 1000.0))
+    }
+
+    #[test]
+    fn jumps_between_subpaths() {
+        let (trace, win) = square_and_line();
+        let samples = 1000;
+        let mut on_square = 0;
+        for i in 0..samples {
+            let p = trace.at(i as f32 / samples as f32 * trace.period, 0.0, win);
+            // never out in the gap between them
+            assert!(p.x <= -45.0 + 1e-3 || p.x >= 45.0 - 1e-3, "{:?}", p);
+            on_square += (p.x < 0.0) as usize;
+        }
+        // and only the subpaths' own lengths share out the time, so the
+        // square, 4 times as long, takes 4 times as long
+        let share = on_square as f32 / samples as f32;
+        assert!((share - 0.8).abs() < 0.01, "{}", share);
+    }
+
+    #[test]
+    fn outlines_need_length() {
+        let mut trace = SvgTrace::default();
+        let dot = vec![Pos::new(1.0, 1.0), Pos::new(1.0, 1.0)];
+        assert!(trace.set_outline(vec![dot]).is_err());
+    }
+
+    // a curve's name, period and scale, and where it is at a given time
+    type Parametric = (&'static str, f32, f32, Box<dyn Fn(f32) -> Pos>);
+
+    // every parametric curve, with a scale and period of its own
+    fn parametric() -> Vec<Parametric> {
+        let circle = Circle {
+            period: 7.0,
+            scale: 120.0,
+        };
+        let figure_eight = FigureEight {
+            period: 9.0,
+            scale: 150.0,
+        };
+        let lissajous = Lissajous {
+            period: 11.0,
+            scale: 200.0,
+            ..Default::default()
+        };
+        let rose = Rose {
+            period: 13.0,
+            scale: 250.0,
+            n: 3,
+            d: 2,
+        };
+        let spiral = Spiral {
+            period: 5.0,
+            scale: 80.0,
+            ..Default::default()
+        };
+        let spline = Spline {
+            period: 3.0,
+            scale: 60.0,
+            ..Default::default()
+        };
+        vec![
+            (
+                "circle",
+                circle.period,
+                circle.scale,
+                Box::new(move |t| circle.at(t, 0.0)),
+            ),
+            (
+                "figure eight",
+                figure_eight.period,
+                figure_eight.scale,
+                Box::new(move |t| figure_eight.at(t, 0.0)),
+            ),
+            (
+                "lissajous",
+                lissajous.period,
+                lissajous.scale,
+                Box::new(move |t| lissajous.at(t, 0.0)),
+            ),
+            (
+                "rose",
+                rose.period,
+                rose.scale,
+                Box::new(move |t| rose.at(t, 0.0)),
+            ),
+            (
+                "spiral",
+                spiral.period,
+                spiral.scale,
+                Box::new(move |t| spiral.at(t, 0.0)),
+            ),
+            (
+                "spline",
+                spline.period,
+                spline.scale,
+                Box::new(move |t| spline.at(t, 0.0)),
+            ),
+        ]
+    }
+
+    #[test]
+    fn curves_loop_once_a_period() {
+        for (name, period, scale, at) in parametric() {
+            for i in 0..200 {
+                let t = i as f32 / 200.0 * period;
+                for loops in [1.0, 3.0] {
+                    let off = (at(t + loops * period) - at(t)).magnitude();
+                    assert!(off < scale * 1e-4, "{} at {}: {}", name, t, off);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn curves_stay_within_their_scale() {
+        for (name, period, scale, at) in parametric() {
+            let (mut widest, mut furthest) = (0.0f32, 0.0f32);
+            for i in 0..2000 {
+                let p = at(i as f32 / 2000.0 * period);
+                widest = widest.max(p.x.abs()).max(p.y.abs());
+                furthest = furthest.max(p.magnitude());
+            }
+            // out to `scale` across or up and down, and no further from
+            // the center, bar a Lissajous figure's corners
+            let reach = match name {
+                "lissajous" => 2f32.sqrt(),
+                _ => 1.0,
+            };
+            assert!((widest / scale - 1.0).abs() < 1e-3, "{}: {}", name, widest);
+            assert!(
+                furthest <= scale * reach * (1.0 + 1e-5),
+                "{}: {}",
+                name,
+                furthest
+            );
+        }
+    }
+}
@@ -0,0 +1,514 @@
+// Flow fields: the direction agents are pushed in at each point of noise space,
+// built from one of several noise sources.
+
+use std::f32::consts::PI;
+
+use nannou::noise::{NoiseFn, OpenSimplex, Perlin, Seedable, Value, Worley};
+use serde::{Deserialize, Serialize};
+
+use crate::pos::Pos;
+
+// Anything agents can steer by. `p` is a point in noise space and time:
+// window coordinates divided by the noise scale, then a z offset picking a
+// slice of the noise (each agent's own, say), then the time (or some other
+// slowly moving value), which slides the slice along z.
+pub trait FlowField: Send + Sync {
+    // the direction to step in, as a unit vector
+    fn direction(&self, p: [f64; 4]) -> Pos;
+}
+
+// a scalar noise function, as fields are built from
+pub type Source = Box<dyn NoiseFn<[f64; 3]> + Send + Sync>;
+
+// a scalar function of noise space and time, as a field reads
+pub type Layer = Box<dyn NoiseFn<[f64; 4]> + Send + Sync>;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NoiseKind {
+    Perlin,
+    OpenSimplex,
+    // flat cells, each with its own constant direction
+    Worley,
+    Value,
+}
+
+impl NoiseKind {
+    // the next kind in the N-key cycle
+    pub fn next(self) -> NoiseKind {
+        match self {
+            NoiseKind::Perlin => NoiseKind::OpenSimplex,
+            NoiseKind::OpenSimplex => NoiseKind::Worley,
+            NoiseKind::Worley => NoiseKind::Value,
+            NoiseKind::Value => NoiseKind::Perlin,
+        }
+    }
+
+    pub fn source(self, seed: u32) -> Source {
+        match self {
+            NoiseKind::Perlin => Box::new(Perlin::new().set_seed(seed)),
+            NoiseKind::OpenSimplex => Box::new(OpenSimplex::new().set_seed(seed)),
+            NoiseKind::Worley => Box::new(Worley::new().set_seed(seed)),
+            NoiseKind::Value => Box::new(Value::new().set_seed(seed)),
+        }
+    }
+}
+
+// How a scalar noise value turns into a direction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FieldMode {
+    // the value is an angle; agents clump where the field converges
+    Angle,
+    // the value is a stream function, and agents follow its curl: a
+    // divergence-free flow that swirls without ever collapsing to a point
+    Curl,
+}
+
+impl FieldMode {
+    // the next mode in the M-key cycle
+    pub fn next(self) -> FieldMode {
+        match self {
+            FieldMode::Angle => FieldMode::Curl,
+            FieldMode::Curl => FieldMode::Angle,
+        }
+    }
+}
+
+// Whether, and how, several octaves of the noise are layered on top of each
+// other.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fractal {
+    // a single octave
+    None,
+    // fractional Brownian motion: finer and fainter octaves summed
+    Fbm,
+    // fBm of folded noise, giving sharp creases
+    Ridged,
+}
+
+impl Fractal {
+    // the next stacking in the F-key cycle
+    pub fn next(self) -> Fractal {
+        match self {
+            Fractal::None => Fractal::Fbm,
+            Fractal::Fbm => Fractal::Ridged,
+            Fractal::Ridged => Fractal::None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub mode: FieldMode,
+    pub noise: NoiseKind,
+    pub fractal: Fractal,
+    // the rest only apply to fbm and ridged
+    pub octaves: usize,
+    // how much finer each octave is than the last
+    pub lacunarity: f64,
+    // how much fainter each octave is than the last
+    pub persistence: f64,
+    // how much faster each octave evolves over time than the last
+    pub time_speed: f64,
+    // domain warping: levels of offsetting each lookup by another lookup of
+    // smooth noise, 0 (off) to 2
+    pub warp_levels: usize,
+    // how far lookups are pushed, in units of noise space
+    pub warp_strength: f64,
+    // size of the warp's features, relative to the field's
+    pub warp_scale: f64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            mode: FieldMode::Angle,
+            noise: NoiseKind::Perlin,
+            fractal: Fractal::None,
+            octaves: 4,
+            lacunarity: 2.0,
+            persistence: 0.5,
+            time_speed: 2.0,
+            warp_levels: 0,
+            warp_strength: 1.0,
+            warp_scale: 1.0,
+        }
+    }
+}
+
+// The octave settings, for stepping interactively.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OctaveControl {
+    Octaves,
+    Lacunarity,
+    Persistence,
+    TimeSpeed,
+}
+
+impl Settings {
+    // nudge one of the octave settings a notch up or down, within sensible
+    // limits, switching on fbm if the noise isn't stacked yet
+    pub fn step(&mut self, control: OctaveControl, up: bool) {
+        let sign = if up { 1.0 } else { -1.0 };
+        match control {
+            OctaveControl::Octaves => {
+                self.octaves = (self.octaves as isize + sign as isize).clamp(1, 8) as usize;
+            }
+            OctaveControl::Lacunarity => {
+                self.lacunarity = (self.lacunarity + 0.1 * sign).clamp(1.1, 4.0);
+            }
+            OctaveControl::Persistence => {
+                self.persistence = (self.persistence + 0.05 * sign).clamp(0.05, 1.0);
+            }
+            OctaveControl::TimeSpeed => {
+                self.time_speed = (self.time_speed + 0.25 * sign).clamp(0.0, 4.0);
+            }
+        }
+        if self.fractal == Fractal::None {
+            self.fractal = Fractal::Fbm;
+        }
+    }
+
+    // a one-line summary of the octave settings, for display
+    pub fn octaves_readout(&self) -> String {
+        let fractal = match self.fractal {
+            Fractal::None => "single octave",
+            Fractal::Fbm => "fbm",
+            Fractal::Ridged => "ridged",
+        };
+        format!(
+            "{}: {} octaves, lacunarity {:.2}, persistence {:.2}, time speed {:.2}",
+            fractal, self.octaves, self.lacunarity, self.persistence, self.time_speed
+        )
+    }
+}
+
+// the field described by `settings`, with all its noise derived from `seed`
+pub fn build(settings: &Settings, seed: u32) -> Box<dyn FlowField> {
+    let source: Layer = match settings.fractal {
+        Fractal::None => Box::new(Slice(settings.noise.source(seed))),
+        Fractal::Fbm | Fractal::Ridged => Box::new(Octaves::new(settings, seed)),
+    };
+    let source: Layer = match settings.warp_levels {
+        0 => source,
+        _ => Box::new(Warp::new(source, settings, seed)),
+    };
+    match settings.mode {
+        FieldMode::Angle => Box::new(Angle(source)),
+        FieldMode::Curl => Box::new(Curl(source)),
+    }
+}
+
+// A noise source in time, its z slice sliding along as time passes.
+pub struct Slice(pub Source);
+
+impl NoiseFn<[f64; 4]> for Slice {
+    fn get(&self, p: [f64; 4]) -> f64 {
+        self.0.get([p[0], p[1], p[2] + p[3]])
+    }
+}
+
+// The noise value read as an angle, a full turn per unit. This is the
+// original perlin sketches' field.
+pub struct Angle(pub Layer);
+
+impl FlowField for Angle {
+    fn direction(&self, p: [f64; 4]) -> Pos {
+        let angle = self.0.get(p) as f32;
+        let angle = angle * 2.0 * PI;
+        Pos::new(angle.cos(), angle.sin())
+    }
+}
+
+// The curl of the noise as a stream function: (dn/dy, -dn/dx), by central
+// differences, so the flow runs along the noise's contour lines. Flat noise
+// (like Worley, away from its cell edges) has no curl, and doesn't push.
+pub struct Curl(pub Layer);
+
+impl Curl {
+    // the curl itself, before it's made a unit vector
+    fn curl(&self, p: [f64; 4]) -> Pos {
+        let eps = 1e-4;
+        let at = |dx: f64, dy: f64| self.0.get([p[0] + dx, p[1] + dy, p[2], p[3]]);
+        let dx = (at(eps, 0.0) - at(-eps, 0.0)) / (2.0 * eps);
+        let dy = (at(0.0, eps) - at(0.0, -eps)) / (2.0 * eps);
+        Pos::new(dy as f32, -dx as f32)
+    }
+}
+
+impl FlowField for Curl {
+    fn direction(&self, p: [f64; 4]) -> Pos {
+        let curl = self.curl(p);
+        match curl.magnitude() {
+            len if len > 0.0 => curl / len,
+            _ => Pos::new(0.0, 0.0),
+        }
+    }
+}
+
+// Several octaves of a noise source summed together, each with its own seed,
+// normalised back to roughly the range of a single octave. Each evolves
+// `time_speed` times faster than the last, but keeps to the same z slice.
+pub struct Octaves {
+    octaves: Vec<Source>,
+    ridged: bool,
+    lacunarity: f64,
+    persistence: f64,
+    time_speed: f64,
+}
+
+impl Octaves {
+    pub fn new(settings: &Settings, seed: u32) -> Octaves {
+        Octaves {
+            octaves: (0..settings.octaves.max(1) as u32)
+                .map(|i| settings.noise.source(seed.wrapping_add(i)))
+                .collect(),
+            ridged: settings.fractal == Fractal::Ridged,
+            lacunarity: settings.lacunarity,
+            persistence: settings.persistence,
+            time_speed: settings.time_speed,
+        }
+    }
+}
+
+impl NoiseFn<[f64; 4]> for Octaves {
+    fn get(&self, p: [f64; 4]) -> f64 {
+        let (mut sum, mut total_amp) = (0.0, 0.0);
+        let (mut freq, mut amp, mut speed) = (1.0, 1.0, 1.0);
+        for octave in self.octaves.iter() {
+            let value = octave.get([p[0] * freq, p[1] * freq, p[2] + p[3] * speed]);
+            // fold each octave at zero into a sharp ridge, keeping it in -1..1
+            let value = match self.ridged {
+                true => (1.0 - value.abs()).powi(2) * 2.0 - 1.0,
+                false => value,
+            };
+            sum += value * amp;
+            total_amp += amp;
+            freq *= self.lacunarity;
+            amp *= self.persistence;
+            speed *= self.time_speed;
+        }
+        sum / total_amp
+    }
+}
+
+// Domain warping: another noise source, with each lookup first pushed
+// around by a pair of Perlin lookups (one each for x and y). With two levels
+// the push is itself pushed, folding the field over on itself.
+pub struct Warp {
+    source: Layer,
+    // an x and y offset noise per level
+    levels: Vec<[Perlin; 2]>,
+    strength: f64,
+    scale: f64,
+}
+
+impl Warp {
+    pub fn new(source: Layer, settings: &Settings, seed: u32) -> Warp {
+        // seeded well clear of the octaves' seeds
+        let warp_seed = |i: usize| Perlin::new().set_seed(seed.wrapping_add(1000 + i as u32));
+        Warp {
+            source,
+            levels: (0..settings.warp_levels.min(2))
+                .map(|level| [warp_seed(level * 2), warp_seed(level * 2 + 1)])
+                .collect(),
+            strength: settings.warp_strength,
+            scale: settings.warp_scale,
+        }
+    }
+}
+
+impl NoiseFn<[f64; 4]> for Warp {
+    fn get(&self, p: [f64; 4]) -> f64 {
+        let mut warped = p;
+        for [wx, wy] in self.levels.iter() {
+            let at = [warped[0] / self.scale, warped[1] / self.scale, p[2] + p[3]];
+            warped = [
+                p[0] + wx.get(at) * self.strength,
+                p[1] + wy.get(at) * self.strength,
+                p[2],
+                p[3],
+            ];
+        }
+        self.source.get(warped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn octaves_speed_up_time_but_not_the_slice() {
+        let settings = Settings {
+            fractal: Fractal::Fbm,
+            octaves: 3,
+            time_speed: 3.0,
+            ..Default::default()
+        };
+        let octaves = Octaves::new(&settings, 7);
+        let (x, y, z, t) = (0.3, -1.2, 2.5, 0.7);
+        // each octave finer, fainter and faster along the same slice
+        let (mut sum, mut total) = (0.0, 0.0);
+        for i in 0..3 {
+            let (freq, amp, speed) = (2f64.powi(i), 0.5f64.powi(i), 3f64.powi(i));
+            let source = settings.noise.source(7 + i as u32);
+            sum += source.get([x * freq, y * freq, z + t * speed]) * amp;
+            total += amp;
+        }
+        assert!((octaves.get([x, y, z, t]) - sum / total).abs() < 1e-12);
+    }
+
+    // points scattered over a few features of noise space, and through time
+    fn scattered() -> impl Iterator<Item = [f64; 4]> {
+        (0..200).map(|i| {
+            let i = i as f64;
+            [
+                (i * 0.37) % 5.0 - 2.5,
+                (i * 0.61) % 5.0 - 2.5,
+                1.5,
+                i * 0.01,
+            ]
+        })
+    }
+
+    fn perlin_curl() -> Curl {
+        Curl(Box::new(Slice(NoiseKind::Perlin.source(5))))
+    }
+
+    #[test]
+    fn curl_directions_are_unit_length() {
+        let curl = perlin_curl();
+        for p in scattered() {
+            assert!(
+                (curl.direction(p).magnitude() - 1.0).abs() < 1e-5,
+                "at {:?}",
+                p
+            );
+        }
+    }
+
+    // the curl of anything has no divergence, so the flow neither gathers
+    // nor spreads; once made unit length it does a little, so this checks
+    // the curl from before then
+    #[test]
+    fn curl_has_no_divergence() {
+        let curl = perlin_curl();
+        let h = 1e-3;
+        let (mut divergence, mut stretch) = (0.0f32, 0.0f32);
+        for p in scattered() {
+            let at = |dx: f64, dy: f64| curl.curl([p[0] + dx, p[1] + dy, p[2], p[3]]);
+            let dvx = (at(h, 0.0).x - at(-h, 0.0).x) / (2.0 * h as f32);
+            let dvy = (at(0.0, h).y - at(0.0, -h).y) / (2.0 * h as f32);
+            divergence = divergence.max((dvx + dvy).abs());
+            stretch = stretch.max(dvx.abs());
+        }
+        // each part on its own is anything but 0
+        assert!(stretch > 0.5, "{}", stretch);
+        assert!(divergence < 1e-2, "{}", divergence);
+    }
+
+    // a layer that's the same everywhere
+    struct Flat(f64);
+
+    impl NoiseFn<[f64; 4]> for Flat {
+        fn get(&self, _: [f64; 4]) -> f64 {
+            self.0
+        }
+    }
+
+    fn close(a: Pos, b: Pos) -> bool {
+        (a - b).magnitude() < 1e-6
+    }
+
+    #[test]
+    fn angles_turn_once_per_unit() {
+        let quarter = std::f32::consts::FRAC_1_SQRT_2;
+        for (value, expected) in [
+            (0.0, Pos::new(1.0, 0.0)),
+            (0.125, Pos::new(quarter, quarter)),
+            (0.25, Pos::new(0.0, 1.0)),
+            (-0.25, Pos::new(0.0, -1.0)),
+            (0.5, Pos::new(-1.0, 0.0)),
+            (1.0, Pos::new(1.0, 0.0)),
+        ] {
+            let angle = Angle(Box::new(Flat(value)));
+            assert!(
+                close(angle.direction([0.3, 0.4, 0.0, 0.0]), expected),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn builds_the_field_its_settings_describe() {
+        let mut every = Vec::new();
+        for mode in [FieldMode::Angle, FieldMode::Curl] {
+            for noise in [
+                NoiseKind::Perlin,
+                NoiseKind::OpenSimplex,
+                NoiseKind::Worley,
+                NoiseKind::Value,
+            ] {
+                for fractal in [Fractal::None, Fractal::Fbm, Fractal::Ridged] {
+                    for warp_levels in 0..3 {
+                        every.push(Settings {
+                            mode,
+                            noise,
+                            fractal,
+                            warp_levels,
+                            ..Default::default()
+                        });
+                    }
+                }
+            }
+        }
+        for settings in every {
+            // without warping, the warp leaves its source alone (as tested
+            // below)
+            let source: Layer = match settings.fractal {
+                Fractal::None => Box::new(Slice(settings.noise.source(9))),
+                _ => Box::new(Octaves::new(&settings, 9)),
+            };
+            let source: Layer = Box::new(Warp::new(source, &settings, 9));
+            let expected: Box<dyn FlowField> = match settings.mode {
+                FieldMode::Angle => Box::new(Angle(source)),
+                FieldMode::Curl => Box::new(Curl(source)),
+            };
+            let built = build(&settings, 9);
+            for p in scattered().take(20) {
+                assert!(
+                    close(built.direction(p), expected.direction(p)),
+                    "{:?} at {:?}",
+                    settings,
+                    p
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn no_warp_levels_leave_the_field_alone() {
+        let settings = Settings {
+            warp_levels: 0,
+            warp_strength: 3.0,
+            ..Default::default()
+        };
+        let warp = Warp::new(Box::new(Slice(NoiseKind::Perlin.source(2))), &settings, 2);
+        let plain = Slice(NoiseKind::Perlin.source(2));
+        for p in scattered() {
+            assert_eq!(warp.get(p), plain.get(p));
+        }
+        // while any level does move it
+        let warped = Settings {
+            warp_levels: 1,
+            ..settings
+        };
+        let warp = Warp::new(Box::new(Slice(NoiseKind::Perlin.source(2))), &warped, 2);
+        assert!(scattered().any(|p| warp.get(p) != plain.get(p)));
+    }
+}
@@ -0,0 +1,151 @@
+// Flocking: steering agents by the neighbours around them (separation,
+// alignment and cohesion, as in Reynolds' boids), on top of whatever else is
+// moving them, so a cloud can move like a murmuration.
+
+use serde::{Deserialize, Serialize};
+
+use crate::grid::Grid;
+use crate::pos::Pos;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub enabled: bool,
+    // how near (in pixels) other agents have to be to count as neighbours
+    pub radius: f32,
+    // push away from neighbours, in pixels per step when they're all right on
+    // top, fading out to nothing as they get to `radius` away
+    pub separation: f32,
+    // fraction of the difference from the neighbours' average step matched
+    // each step
+    pub alignment: f32,
+    // fraction of the distance to the neighbours' centre covered each step
+    pub cohesion: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            enabled: false,
+            radius: 40.0,
+            separation: 3.0,
+            alignment: 0.3,
+            cohesion: 0.02,
+        }
+    }
+}
+
+// The flocking step for an agent at `pos` that last moved by `vel`, given
+// every agent's position in `grid` and last step in `vels` (by the grid's
+// indices). Agents exactly on top of `pos`, itself included, are ignored.
+pub fn steer(pos: Pos, vel: Pos, grid: &Grid, vels: &[Pos], settings: &Settings) -> Pos {
+    let mut push = Pos::new(0.0, 0.0);
+    let mut vel_sum = Pos::new(0.0, 0.0);
+    let mut pos_sum = Pos::new(0.0, 0.0);
+    let mut count = 0;
+    for (index, other) in grid.near(pos, settings.radius) {
+        let away = pos - other;
+        let dist = away.magnitude();
+        if dist < f32::EPSILON {
+            continue;
+        }
+        push = push + away / dist * (1.0 - dist / settings.radius);
+        vel_sum = vel_sum + vels[index];
+        pos_sum = pos_sum + other;
+        count += 1;
+    }
+    if count == 0 {
+        return Pos::new(0.0, 0.0);
+    }
+    let n = count as f32;
+    let separation = push / n * settings.separation;
+    let alignment = (vel_sum / n - vel) * settings.alignment;
+    let cohesion = (pos_sum / n - pos) * settings.cohesion;
+    separation + alignment + cohesion
+}
+
+#[cfg(test)]
+mod tests {
+    use nannou::geom::Rect;
+
+    use super::*;
+
+    // how the first of `points` steers, each point having last moved by the
+    // matching one of `vels`, with only the given rules at work
+    fn steer_first(points: &[Pos], vels: &[Pos], settings: &Settings) -> Pos {
+        let mut grid = Grid::new(Rect::from_w_h(400.0, 400.0), settings.radius);
+        grid.rebuild(points.iter().copied());
+        steer(points[0], vels[0], &grid, vels, settings)
+    }
+
+    fn only(separation: f32, alignment: f32, cohesion: f32) -> Settings {
+        Settings {
+            enabled: true,
+            radius: 40.0,
+            separation,
+            alignment,
+            cohesion,
+        }
+    }
+
+    fn close(a: Pos, b: Pos) -> bool {
+        (a - b).magnitude() < 1e-5
+    }
+
+    const STILL: Pos = Pos { x: 0.0, y: 0.0 };
+
+    #[test]
+    fn separation_pushes_away_more_the_nearer() {
+        let settings = only(2.0, 0.0, 0.0);
+        let (vels, here) = ([STILL; 2], Pos::new(0.0, 0.0));
+        let near = steer_first(&[here, Pos::new(10.0, 0.0)], &vels, &settings);
+        let far = steer_first(&[here, Pos::new(30.0, 0.0)], &vels, &settings);
+        // a quarter and three quarters of the way out
+        assert!(close(near, Pos::new(-1.5, 0.0)), "{:?}", near);
+        assert!(close(far, Pos::new(-0.5, 0.0)), "{:?}", far);
+        // and from two sides at once, away from both
+        let between = steer_first(
+            &[here, Pos::new(0.0, 10.0), Pos::new(10.0, 0.0)],
+            &[STILL; 3],
+            &settings,
+        );
+        assert!(close(between, Pos::new(-0.75, -0.75)), "{:?}", between);
+    }
+
+    #[test]
+    fn alignment_matches_the_neighbours_step() {
+        let settings = only(0.0, 0.5, 0.0);
+        let points = [Pos::new(0.0, 0.0), Pos::new(10.0, 0.0), Pos::new(0.0, 10.0)];
+        let vels = [Pos::new(1.0, 0.0), Pos::new(0.0, 2.0), Pos::new(0.0, 4.0)];
+        // half of the way from (1, 0) to their average of (0, 3)
+        let steer = steer_first(&points, &vels, &settings);
+        assert!(close(steer, Pos::new(-0.5, 1.5)), "{:?}", steer);
+    }
+
+    #[test]
+    fn cohesion_pulls_towards_the_neighbours_centre() {
+        let settings = only(0.0, 0.0, 0.1);
+        let points = [Pos::new(0.0, 0.0), Pos::new(20.0, 0.0), Pos::new(0.0, 20.0)];
+        let steer = steer_first(&points, &[STILL; 3], &settings);
+        assert!(close(steer, Pos::new(1.0, 1.0)), "{:?}", steer);
+    }
+
+    #[test]
+    fn only_neighbours_within_the_radius_count() {
+        let settings = only(2.0, 0.5, 0.1);
+        let here = Pos::new(0.0, 0.0);
+        let vels = [STILL, Pos::new(3.0, 0.0)];
+        for outside in [
+            Pos::new(40.5, 0.0),
+            Pos::new(30.0, 30.0),
+            Pos::new(-150.0, 90.0),
+        ] {
+            assert_eq!(steer_first(&[here, outside], &vels, &settings), STILL);
+        }
+        let inside = steer_first(&[here, Pos::new(39.0, 0.0)], &vels, &settings);
+        assert!(inside.magnitude() > 0.0);
+        // and on its own, or on top of another, it isn't steered at all
+        assert_eq!(steer_first(&[here], &[STILL], &settings), STILL);
+        assert_eq!(steer_first(&[here, here], &vels, &settings), STILL);
+    }
+}
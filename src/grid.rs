@@ -0,0 +1,168 @@
+// A uniform grid over a set of points, for finding every point within some
+// radius of a position without looking at all of them.
+
+// Points are bucketed into square cells of `cell_size` and stored sorted by
+// cell, row by row, so a run of cells along a row is one contiguous slice.
+// Points outside the bounds are kept in the nearest edge cell, so queries stay
+// exact, just slower, for anything that strays out.
+
+use nannou::geom::Rect;
+
+use crate::pos::Pos;
+
+// the most cells along either side; a smaller cell size than this allows only
+// costs memory, as points are never found further than a cell or two apart
+const MAX_CELLS_ACROSS: f32 = 1024.0;
+
+pub struct Grid {
+    bounds: Rect,
+    cell_size: f32,
+    cols: usize,
+    rows: usize,
+    // cell i holds entries[cell_start[i]..cell_start[i + 1]]
+    cell_start: Vec<usize>,
+    // (index into the original points, position), sorted by cell
+    entries: Vec<(usize, Pos)>,
+}
+
+impl Grid {
+    // an empty grid; a `cell_size` that's infinite, or larger than the bounds,
+    // gives a single cell holding everything, and one too small to be worth
+    // it is made bigger, so there are never more than `MAX_CELLS_ACROSS`
+    // cells a side
+    pub fn new(bounds: Rect, cell_size: f32) -> Grid {
+        let cell_size = cell_size.max(bounds.w().max(bounds.h()) / MAX_CELLS_ACROSS);
+        let cols = ((bounds.w() / cell_size).ceil() as usize).max(1);
+        let rows = ((bounds.h() / cell_size).ceil() as usize).max(1);
+        Grid {
+            bounds,
+            cell_size,
+            cols,
+            rows,
+            cell_start: vec![0; cols * rows + 1],
+            entries: Vec::new(),
+        }
+    }
+
+    // replace the contents of the grid, reusing its buffers
+    pub fn rebuild(&mut self, points: impl ExactSizeIterator<Item = Pos> + Clone) {
+        // count the points in each cell, then sum the counts so each cell
+        // starts out holding the offset just past its end
+        self.cell_start.iter_mut().for_each(|n| *n = 0);
+        for pos in points.clone() {
+            let cell = self.cell(pos);
+            self.cell_start[cell] += 1;
+        }
+        for i in 1..self.cell_start.len() {
+            self.cell_start[i] += self.cell_start[i - 1];
+        }
+        // then fill each cell from the back, which leaves its offset pointing
+        // at its start
+        self.entries.clear();
+        self.entries.resize(points.len(), (0, Pos::new(0.0, 0.0)));
+        for (index, pos) in points.enumerate() {
+            let cell = self.cell(pos);
+            self.cell_start[cell] -= 1;
+            self.entries[self.cell_start[cell]] = (index, pos);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // every point within `radius` of `pos`, including any at `pos` itself, as
+    // (index, position); pass an infinite radius for all of them
+    pub fn near(&self, pos: Pos, radius: f32) -> impl Iterator<Item = (usize, Pos)> + '_ {
+        let (col0, row0) = self.col_row(Pos::new(pos.x - radius, pos.y - radius));
+        let (col1, row1) = self.col_row(Pos::new(pos.x + radius, pos.y + radius));
+        let radius_sq = radius * radius;
+        (row0..=row1)
+            .flat_map(move |row| {
+                let start = self.cell_start[row * self.cols + col0];
+                let end = self.cell_start[row * self.cols + col1 + 1];
+                self.entries[start..end].iter().copied()
+            })
+            .filter(move |(_, p)| {
+                let d = *p - pos;
+                d.x * d.x + d.y * d.y <= radius_sq
+            })
+    }
+
+    fn col_row(&self, pos: Pos) -> (usize, usize) {
+        // float to int casts saturate, so far off (or infinite) positions
+        // clamp to the edge cells too
+        let col = ((pos.x - self.bounds.left()) / self.cell_size).floor() as isize;
+        let row = ((pos.y - self.bounds.bottom()) / self.cell_size).floor() as isize;
+        (
+            col.clamp(0, self.cols as isize - 1) as usize,
+            row.clamp(0, self.rows as isize - 1) as usize,
+        )
+    }
+
+    fn cell(&self, pos: Pos) -> usize {
+        let (col, row) = self.col_row(pos);
+        row * self.cols + col
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a scatter of points, some outside the bounds
+    fn points() -> Vec<Pos> {
+        (0..200)
+            .map(|i| {
+                let t = i as f32 * 0.7;
+                Pos::new(t.sin() * 130.0 * (i % 7) as f32 / 5.0, t.cos() * 90.0)
+            })
+            .collect()
+    }
+
+    fn near_sorted(grid: &Grid, pos: Pos, radius: f32) -> Vec<usize> {
+        let mut found: Vec<usize> = grid.near(pos, radius).map(|(i, _)| i).collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn near_matches_brute_force() {
+        let points = points();
+        let mut grid = Grid::new(Rect::from_w_h(300.0, 200.0), 25.0);
+        grid.rebuild(points.iter().copied());
+        assert_eq!(grid.len(), points.len());
+        for pos in [
+            Pos::new(0.0, 0.0),
+            Pos::new(140.0, -95.0),
+            Pos::new(-400.0, 10.0),
+        ] {
+            for radius in [1.0, 25.0, 60.0, 1000.0] {
+                let expected: Vec<usize> = (0..points.len())
+                    .filter(|&i| (points[i] - pos).magnitude() <= radius)
+                    .collect();
+                assert_eq!(near_sorted(&grid, pos, radius), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn infinite_radius_finds_everything() {
+        let points = points();
+        let mut grid = Grid::new(Rect::from_w_h(300.0, 200.0), f32::INFINITY);
+        grid.rebuild(points.iter().copied());
+        let all: Vec<usize> = (0..points.len()).collect();
+        assert_eq!(near_sorted(&grid, Pos::new(0.0, 0.0), f32::INFINITY), all);
+    }
+
+    #[test]
+    fn tiny_cells_are_capped() {
+        let grid = Grid::new(Rect::from_w_h(300.0, 200.0), 1e-6);
+        assert!(grid.cols <= MAX_CELLS_ACROSS as usize);
+        assert!(grid.rows <= MAX_CELLS_ACROSS as usize);
+    }
+}
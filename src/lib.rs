@@ -0,0 +1,34 @@
+// Simulation core shared by the duststorm visualisers.
+//
+// Nothing in here touches nannou's `App` or `Frame`, so the simulations can be
+// created and stepped headlessly; the binaries under `src/bin` are thin
+// front-ends that feed in time and input, and draw the resulting state.
+
+pub mod accum;
+pub mod agent;
+pub mod canvas;
+pub mod cellfill;
+pub mod cli;
+pub mod clock;
+pub mod color;
+pub mod colormap;
+pub mod curve;
+pub mod field;
+pub mod flock;
+pub mod grid;
+pub mod motion;
+pub mod painter;
+pub mod par;
+pub mod perlin;
+pub mod perlin2;
+pub mod picture;
+pub mod plot;
+pub mod pos;
+pub mod preset;
+pub mod rng;
+pub mod svg;
+pub mod svgpath;
+pub mod target;
+pub mod trail;
+pub mod voronoi;
+pub mod wanderer;
@@ -0,0 +1,35 @@
+// How agents move: either jumping straight to where the noise and target send
+// them each frame, as the sketches originally did, or as bodies with mass and
+// velocity that those push around, for smooth trails whatever the frame rate.
+
+use serde::{Deserialize, Serialize};
+
+// The frame rate the per-frame step sizes and pulls were tuned at. With
+// inertia, forces are scaled so that an agent settles at the same speed it
+// would have stepped at this frame rate.
+pub const REFERENCE_FPS: f32 = 60.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    // off to step a fixed distance per frame, whatever the time between
+    // frames
+    pub inertia: bool,
+    pub mass: f32,
+    // how strongly velocity is damped, per second; against a steady push an
+    // agent gets up to speed in about 1/drag seconds (scaled by mass)
+    pub drag: f32,
+    // in pixels per second
+    pub max_speed: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            inertia: false,
+            mass: 1.0,
+            drag: 4.0,
+            max_speed: 1500.0,
+        }
+    }
+}
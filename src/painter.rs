@@ -0,0 +1,97 @@
+// The handful of drawing primitives the visualisers need, so the same drawing
+// code can target either a nannou window or the software `Canvas`.
+
+use nannou::geom::{Rect, Vec2};
+use nannou::Draw;
+
+use crate::color::Color;
+use crate::pos::Pos;
+
+pub trait Painter {
+    // clear everything to an opaque colour
+    fn background(&mut self, color: Color);
+    // blend a (usually translucent) colour over everything, fading what's
+    // already been drawn
+    fn fade(&mut self, color: Color);
+    fn point(&mut self, pos: Pos, radius: f32, color: Color);
+    fn line(&mut self, start: Pos, end: Pos, weight: f32, color: Color);
+    // an open line through `points`, its colour blending from each point's
+    // to the next
+    fn polyline(&mut self, points: &[(Pos, Color)], weight: f32);
+    fn polyline_closed(&mut self, points: &[Pos], weight: f32, color: Color);
+    // fill the inside of a closed outline
+    fn polygon(&mut self, points: &[Pos], color: Color);
+}
+
+// paints through a nannou `Draw` covering the window `win`
+pub struct DrawPainter<'a> {
+    draw: &'a Draw,
+    win: Rect,
+}
+
+impl<'a> DrawPainter<'a> {
+    pub fn new(draw: &'a Draw, win: Rect) -> Self {
+        DrawPainter { draw, win }
+    }
+}
+
+fn nannou_color(color: Color) -> nannou::color::Srgba {
+    nannou::color::srgba(color.red, color.green, color.blue, color.alpha)
+}
+
+fn vec2(pos: Pos) -> Vec2 {
+    Vec2::new(pos.x, pos.y)
+}
+
+impl Painter for DrawPainter<'_> {
+    fn background(&mut self, color: Color) {
+        self.draw.background().color(nannou_color(color));
+    }
+
+    fn fade(&mut self, color: Color) {
+        self.draw
+            .rect()
+            .x_y(self.win.x(), self.win.y())
+            .wh(self.win.wh())
+            .color(nannou_color(color));
+    }
+
+    fn point(&mut self, pos: Pos, radius: f32, color: Color) {
+        self.draw
+            .ellipse()
+            .x_y(pos.x, pos.y)
+            .radius(radius)
+            .color(nannou_color(color));
+    }
+
+    fn line(&mut self, start: Pos, end: Pos, weight: f32, color: Color) {
+        self.draw
+            .line()
+            .start(vec2(start))
+            .end(vec2(end))
+            .weight(weight)
+            .color(nannou_color(color));
+    }
+
+    fn polyline(&mut self, points: &[(Pos, Color)], weight: f32) {
+        self.draw
+            .polyline()
+            .weight(weight)
+            .points_colored(points.iter().map(|(p, c)| (vec2(*p), nannou_color(*c))));
+    }
+
+    fn polyline_closed(&mut self, points: &[Pos], weight: f32, color: Color) {
+        self.draw
+            .polyline()
+            .weight(weight)
+            .points_closed(points.iter().map(|p| vec2(*p)))
+            .color(nannou_color(color));
+    }
+
+    fn polygon(&mut self, points: &[Pos], color: Color) {
+        self.draw
+            .polygon()
+            .color(nannou_color(color))
+            .points(points.iter().map(|p| vec2(*p)));
+    }
+}
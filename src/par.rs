@@ -0,0 +1,86 @@
+// Stepping every agent of a sim, spread across threads when built with the
+// `parallel` feature.
+
+// Each agent only reads the shared state of its sim and draws from its own
+// random stream, so the order agents are stepped in doesn't matter and both
+// paths give exactly the same results.
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+#[cfg(feature = "parallel")]
+pub fn for_each_mut<T, F>(items: &mut [T], f: F)
+where
+    T: Send,
+    F: Fn(&mut T) + Send + Sync,
+{
+    items.par_iter_mut().for_each(f);
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn for_each_mut<T, F>(items: &mut [T], f: F)
+where
+    F: Fn(&mut T),
+{
+    items.iter_mut().for_each(f);
+}
+
+// Only built with `--features parallel`; without it there's just the one
+// path, and nothing to compare.
+#[cfg(all(test, feature = "parallel"))]
+mod tests {
+    use nannou::geom::Rect;
+    use rayon::ThreadPoolBuilder;
+
+    use crate::pos::Pos;
+    use crate::{perlin, perlin2};
+
+    const STEPS: usize = 120;
+    const DT: f32 = 1.0 / 60.0;
+
+    // `f` run with all its parallel work spread over `threads` threads
+    fn on_threads<T: Send>(threads: usize, f: impl FnOnce() -> T + Send) -> T {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("couldn't build a thread pool");
+        pool.install(f)
+    }
+
+    fn perlin(seed: u64) -> Vec<Pos> {
+        let mut settings = perlin::Settings {
+            agent_count: 500,
+            ..Default::default()
+        };
+        settings.flock.enabled = true;
+        settings.motion.inertia = true;
+        let mut sim = perlin::Sim::new(Rect::from_w_h(800.0, 600.0), seed, settings);
+        for step in 0..STEPS {
+            sim.update(step as f32 * DT, DT, Pos::new(0.0, 0.0));
+        }
+        sim.agents_pos()
+    }
+
+    // long enough that plenty of agents respawn
+    fn perlin2(seed: u64) -> Vec<Pos> {
+        let settings = perlin2::Settings {
+            agent_count: 500,
+            ..Default::default()
+        };
+        let mut sim = perlin2::Sim::new(Rect::from_w_h(800.0, 600.0), seed, settings);
+        for step in 0..STEPS * 3 {
+            sim.update(step as f32 * DT, DT);
+        }
+        sim.agents_pos()
+    }
+
+    // one thread steps the agents in order, as the serial path does
+    #[test]
+    fn same_results_on_any_number_of_threads() {
+        let (perlin_alone, perlin2_alone) = on_threads(1, || (perlin(3), perlin2(3)));
+        for threads in [2, 8] {
+            assert_eq!(on_threads(threads, || perlin(3)), perlin_alone);
+            assert_eq!(on_threads(threads, || perlin2(3)), perlin2_alone);
+        }
+    }
+}
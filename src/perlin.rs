@@ -1,240 +1,402 @@
-// Agents that move following a target, while being pushed around by Perlin noise.
+// Agents that move following a target, while being pushed around by Perlin
+// noise.
 
-use std::collections::VecDeque;
+use nannou::geom::Rect;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use nannou::noise::{NoiseFn, Perlin, Seedable};
-use nannou::prelude::*;
-use palette::convert::FromColorUnclamped;
+use crate::agent::Agent;
+use crate::color::{Color, ColorMode, BLACK, RED};
+use crate::colormap::{self, ColorInput};
+use crate::curve;
+use crate::field::{self, FlowField};
+use crate::flock;
+use crate::grid::Grid;
+use crate::motion;
+use crate::painter::Painter;
+use crate::par;
+use crate::pos::Pos;
+use crate::rng::{self, SimRng};
+use crate::target::{self, Assignment, Target, TargetMode};
+use crate::trail::Trails;
 
-pub mod pos;
-use pos::Pos;
-pub mod agent;
-use agent::Agent;
-
-fn main() {
-    nannou::app(model).update(update).run();
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub agent_count: usize,
+    pub noise_scale: f64,
+    // the noise agents steer by
+    pub field: field::Settings,
+    pub step_size: f32, // in pixels per frame
+    // whether agents have inertia, and if so how heavy and damped they are
+    pub motion: motion::Settings,
+    // fraction of the distance to the target covered each step; tweak for
+    // best results
+    pub target_accel: f32,
+    // how many frames long each agent's trail is
+    pub history_length: usize,
+    // how it fades over that time: 0 keeps it solid to the end, 1 fades it
+    // out steadily, and higher fades it sooner
+    pub trail_fade: f32,
+    // the first target's mode
+    pub target_mode: TargetMode,
+    // any more targets, each following its own path, and how agents are
+    // shared out between them all
+    pub extra_targets: Vec<TargetMode>,
+    pub assignment: Assignment,
+    // the shapes of the curve target modes
+    pub curves: curve::Settings,
+    // the colour mode, cycled with C, and the palettes and input behind it
+    pub color_mode: ColorMode,
+    pub colors: colormap::Settings,
+    pub flock: flock::Settings,
 }
 
-enum TargetMode {
-    Circle,
-    FigureEight,
-    Noise,
-    Average,
-    Mouse,
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            agent_count: 100,
+            noise_scale: 400.0,
+            field: field::Settings::default(),
+            step_size: 5.0,
+            motion: motion::Settings::default(),
+            target_accel: 0.03,
+            history_length: 300,
+            trail_fade: 1.0,
+            target_mode: TargetMode::Circle,
+            extra_targets: Vec::new(),
+            assignment: Assignment::Nearest,
+            curves: curve::Settings::default(),
+            color_mode: ColorMode::White,
+            colors: colormap::Settings::default(),
+            flock: flock::Settings::default(),
+        }
+    }
 }
 
-enum ColorMode {
-    White,
-    RedBlue,
-    HueRotate,
+impl Settings {
+    // every target's mode, the first one first
+    pub fn target_modes(&self) -> Vec<TargetMode> {
+        let mut modes = vec![self.target_mode];
+        modes.extend_from_slice(&self.extra_targets);
+        modes
+    }
 }
 
-struct Model {
-    perlin: Perlin,
-    noise_seed: u32,
-    noise_scale: f64,
+pub struct Sim {
+    pub field: Box<dyn FlowField>,
+    pub noise_seed: u32,
+    pub noise_scale: f64,
     pub agents: Vec<Agent>,
-    win: Rect,
-    pub target: Pos,
-    pub target_mode: TargetMode,
-    pub color_mode: ColorMode,
-    pub draw_target: bool,
-    pub agents_history: VecDeque<Vec<Agent>>,
+    pub win: Rect,
+    pub targets: Vec<Target>,
+    pub trails: Trails,
+    pub seed: u64,
+    pub settings: Settings,
+    // the agents bucketed by position, rebuilt each update while flocking
+    grid: Grid,
+    rng: SimRng,
 }
 
-impl Model {
-    pub fn new(win: Rect) -> Self {
-        let agent_count = 100;
-        let noise_scale = 400.0;
-        let noise_seed = random::<u32>();
-        let perlin = Perlin::new().set_seed(noise_seed);
-        let agents = (0..agent_count)
-            .map(|_| Agent::new(win, true, 5f32))
+impl Sim {
+    pub fn new(win: Rect, seed: u64, settings: Settings) -> Self {
+        let mut rng = rng::from_seed(seed);
+        let noise_scale = settings.noise_scale;
+        let noise_seed = rng.gen::<u32>();
+        let field = field::build(&settings.field, noise_seed);
+        let agents = (0..settings.agent_count)
+            .map(|_| Agent::new(win, true, settings.step_size, settings.motion, &mut rng))
+            .collect();
+        let targets = settings
+            .target_modes()
+            .into_iter()
+            .map(Target::new)
             .collect();
-        let target = Pos::new(0f32, 0f32);
-        let target_mode = TargetMode::Circle;
-        let color_mode = ColorMode::White;
-        let draw_target = false;
-        let agents_history = VecDeque::new();
-
-        Model {
-            perlin,
+        let trails = Trails::new(settings.history_length);
+        let grid = Grid::new(win, settings.flock.radius);
+
+        let mut sim = Sim {
+            field,
             noise_seed,
             noise_scale,
             agents,
             win,
-            target,
-            target_mode,
-            color_mode,
-            draw_target,
-            agents_history,
-        }
+            targets,
+            trails,
+            seed,
+            settings,
+            grid,
+            rng,
+        };
+        sim.assign_targets();
+        sim
     }
 
     pub fn reset_agents(&mut self) {
+        self.trails.clear();
         self.agents = (0..self.agents.len())
-            .map(|_| Agent::new(self.win, true, 5f32))
+            .map(|_| {
+                Agent::new(
+                    self.win,
+                    true,
+                    self.settings.step_size,
+                    self.settings.motion,
+                    &mut self.rng,
+                )
+            })
             .collect();
+        self.assign_targets();
     }
 
-    pub fn _agents_pos(&self) -> Vec<Pos> {
-        self.agents.iter().map(|agent| agent.pos).collect()
+    pub fn reseed(&mut self) {
+        self.noise_seed = self.rng.gen_range(0..10000);
+        self.field = field::build(&self.settings.field, self.noise_seed);
     }
-}
 
-fn model(app: &App) -> Model {
-    app.new_window()
-        .size(1000, 1000)
-        .view(view)
-        .key_released(key_released)
-        .build()
-        .unwrap();
-    Model::new(app.window_rect())
-}
-
-fn update(app: &App, model: &mut Model, _update: Update) {
-    // agents target a point on the canvas that updates according to the
-    // selected draw mode:
-    model.target = match model.target_mode {
-        TargetMode::Circle => {
-            // tracks a circle moving clockwise around the canvas center
-            let theta = app.time * PI * -0.2;
-            let r = 300f32;
-            Pos::new(r * theta.cos(), r * theta.sin())
-        }
-        TargetMode::FigureEight => {
-            // tracks a vertical figure eight, twice as tall as wide
-            let theta = app.time * PI * -0.2;
-            let r = 300f32;
-            Pos::new(r / 2.0 * -(theta * 2.0).sin(), r * theta.sin())
+    // switch to new settings on the fly, keeping as much of the current state
+    // as possible
+    pub fn apply_settings(&mut self, settings: Settings) {
+        self.noise_scale = settings.noise_scale;
+        if settings.field != self.settings.field {
+            self.field = field::build(&settings.field, self.noise_seed);
         }
-        TargetMode::Noise => {
-            let noise = model.perlin.get([
-                model.target.x as f64 / model.noise_scale,
-                model.target.y as f64 / model.noise_scale,
-                app.time as f64,
-            ]) as f32;
-            let noise = noise * 2.0 * PI;
-            let target = model.target + Pos::new(noise.cos(), noise.sin()) * 30.0;
-            target.pow(0.99)
-        }
-        TargetMode::Average => {
-            // tracks the average of the newest agent set, with an attraction
-            // factor to canvas center
-            let target = model
-                .agents_history
-                .iter()
-                .last()
-                .map(|agents| {
-                    agents
-                        .iter()
-                        .map(|a| a.pos)
-                        .reduce(|acc, pos| acc + pos)
-                        .unwrap_or(Pos::new(0.0, 0.0))
-                        / agents.len() as f32
-                })
-                .unwrap_or(Pos::new(0.0, 0.0));
-            target.pow(0.95)
+        self.resize_targets(settings.target_modes());
+        // the trails would start afresh at the next update anyway, and until
+        // then they'd be the wrong width to paint
+        if settings.agent_count != self.agents.len() {
+            self.trails.clear();
         }
-        TargetMode::Mouse => {
-            // the current mouse position
-            Pos::new(app.mouse.x, app.mouse.y)
+        self.agents.truncate(settings.agent_count);
+        while self.agents.len() < settings.agent_count {
+            let agent = Agent::new(
+                self.win,
+                true,
+                settings.step_size,
+                settings.motion,
+                &mut self.rng,
+            );
+            self.agents.push(agent);
         }
-    };
-    model
-        .agents
-        .iter_mut()
-        .for_each(|a| a.update1(model.perlin, model.target, model.noise_scale));
+        self.agents
+            .iter_mut()
+            .for_each(|a| a.step_size = settings.step_size);
+        self.set_motion(settings.motion);
+        self.trails.set_length(settings.history_length);
+        self.grid = Grid::new(self.win, settings.flock.radius);
+        self.settings = settings;
+        self.assign_targets();
+    }
 
-    if model.agents_history.len() >= 300 {
-        let _ = model.agents_history.pop_front();
+    // switch to a different flow field, keeping the noise seed
+    pub fn set_field(&mut self, settings: field::Settings) {
+        self.field = field::build(&settings, self.noise_seed);
+        self.settings.field = settings;
     }
-    model.agents_history.push_back(model.agents.clone());
-}
 
-fn view(app: &App, model: &Model, frame: Frame) {
-    let draw = app.draw();
-    if app.keys.down.contains(&Key::R) {
-        draw.background().color(BLACK);
-    }
-    // 'erase' the oldest agent set by overwriting in black
-    model.agents_history.iter().nth(0).map(|agents| {
-        agents.iter().for_each(|agent| {
-            draw.ellipse()
-                .x_y(agent.pos.x, agent.pos.y)
-                .radius(0.8)
-                .color(BLACK);
-        })
-    });
-    // draw the newest agent set
-    model.agents_history.iter().last().map(|agents| {
-        agents.iter().for_each(|agent| {
-            let color = match model.color_mode {
-                ColorMode::White => WHITE,
-                ColorMode::RedBlue => rgb(
-                    15 + (agent.z_offset * 240.0) as u8,
-                    0,
-                    255 - (agent.z_offset * 240.0) as u8,
-                ),
-                ColorMode::HueRotate => {
-                    let (r, g, b) =
-                        palette::rgb::Rgb::from_color_unclamped(palette::Hsv::new_srgb(
-                            50.0 + agent.z_offset * 300.0,
-                            0.5 + agent.z_offset * 0.5,
-                            1.0,
-                        ))
-                        .into_format::<u8>()
-                        .into_components();
-                    rgb(r, g, b)
-                }
-            };
-            draw.ellipse()
-                .x_y(agent.pos.x, agent.pos.y)
-                .radius(0.5)
-                .color(color);
-        })
-    });
-    if model.draw_target {
-        draw.ellipse()
-            .x_y(model.target.x, model.target.y)
-            .radius(1.0)
-            .color(RED);
-    }
-    draw.to_frame(&app, &frame).unwrap();
-}
+    // switch agents between stepping and inertia, or change how they move
+    // with it
+    pub fn set_motion(&mut self, motion: motion::Settings) {
+        self.agents.iter_mut().for_each(|a| a.motion = motion);
+        self.settings.motion = motion;
+    }
 
-fn key_released(_app: &App, model: &mut Model, key: Key) {
-    match key {
-        Key::D => {
-            model.target_mode = match model.target_mode {
-                TargetMode::Circle => TargetMode::FigureEight,
-                TargetMode::FigureEight => TargetMode::Noise,
-                TargetMode::Noise => TargetMode::Average,
-                TargetMode::Average => TargetMode::Mouse,
-                TargetMode::Mouse => TargetMode::Circle,
-            };
-        }
-        Key::C => {
-            model.color_mode = match model.color_mode {
-                ColorMode::White => ColorMode::RedBlue,
-                ColorMode::RedBlue => ColorMode::HueRotate,
-                ColorMode::HueRotate => ColorMode::White,
-            };
+    // switch to a new set of targets, one per mode, keeping where the
+    // existing ones have got to
+    pub fn set_targets(&mut self, modes: Vec<TargetMode>) {
+        self.resize_targets(modes);
+        self.settings.target_mode = self.targets[0].mode;
+        self.settings.extra_targets = self.targets[1..].iter().map(|t| t.mode).collect();
+        self.assign_targets();
+    }
+
+    pub fn set_assignment(&mut self, assignment: Assignment) {
+        self.settings.assignment = assignment;
+        self.assign_targets();
+    }
+
+    pub fn target_modes(&self) -> Vec<TargetMode> {
+        self.targets.iter().map(|t| t.mode).collect()
+    }
+
+    fn resize_targets(&mut self, modes: Vec<TargetMode>) {
+        self.targets.truncate(modes.len());
+        for (i, mode) in modes.into_iter().enumerate() {
+            match self.targets.get_mut(i) {
+                Some(target) => target.mode = mode,
+                None => self.targets.push(Target::new(mode)),
+            }
         }
-        Key::T => {
-            model.draw_target = match model.draw_target {
-                false => true,
-                true => false,
+    }
+
+    // share the agents out between the targets; nearest is worked out afresh
+    // every update too
+    fn assign_targets(&mut self) {
+        let assignment = self.settings.assignment;
+        target::assign(assignment, &self.targets, &mut self.agents, &mut self.rng);
+    }
+
+    pub fn agents_pos(&self) -> Vec<Pos> {
+        self.agents.iter().map(|agent| agent.pos).collect()
+    }
+
+    // advance one frame; `time` is seconds since start, `dt` seconds since the
+    // previous update, and `mouse` the cursor position (only used by
+    // `TargetMode::Mouse`)
+    pub fn update(&mut self, time: f32, dt: f32, mouse: Pos) {
+        let next: Vec<Pos> = (0..self.targets.len())
+            .map(|i| self.next_target(i, time, mouse))
+            .collect();
+        self.targets
+            .iter_mut()
+            .zip(next)
+            .for_each(|(target, pos)| target.pos = pos);
+        let (field, targets, noise_scale) = (&*self.field, &self.targets, self.noise_scale);
+        let nearest = self.settings.assignment == Assignment::Nearest;
+        let (target_accel, flock) = (self.settings.target_accel, &self.settings.flock);
+        // everyone's position and last step, as they were before anyone moves
+        let vels: Vec<Pos> = match flock.enabled {
+            true => {
+                self.grid.rebuild(self.agents.iter().map(|a| a.pos));
+                self.agents.iter().map(Agent::last_step).collect()
+            }
+            false => Vec::new(),
+        };
+        let grid = &self.grid;
+        par::for_each_mut(&mut self.agents, |a| {
+            let steer = match flock.enabled {
+                true => flock::steer(a.pos, a.last_step(), grid, &vels, flock),
+                false => Pos::new(0.0, 0.0),
             };
+            if nearest {
+                a.target = target::nearest(targets, a.pos);
+            }
+            let target = targets[a.target].pos;
+            a.update1(field, target, noise_scale, target_accel, steer, dt)
+        });
+
+        // each agent's colour as it is now, for its trail to keep
+        let (colors, mode) = (&self.settings.colors, self.settings.color_mode);
+        let samples = self.agents.iter().map(|a| {
+            let target = self.targets[a.target].pos;
+            let t = colors.position(a, 0.0, target, self.win);
+            (a.pos, colors.color(mode, t))
+        });
+        self.trails.push(samples);
+    }
+
+    // draw every agent's trail from scratch as one line, fading with age; the
+    // newest end is drawn `alpha` of the way through the last update, from 0
+    // (where agents were before it) to 1 (where they are now)
+    pub fn paint<P: Painter>(&self, painter: &mut P, draw_target: bool, alpha: f32) {
+        painter.background(BLACK);
+        let colors = &self.settings.colors;
+        let length = self.trails.length() as f32;
+        // oldest first
+        let frames: Vec<_> = (0..self.trails.frames())
+            .rev()
+            .map(|age| {
+                let fade = (1.0 - age as f32 / length).powf(self.settings.trail_fade);
+                // coloured by age, a whole frame is the same colour, which
+                // changes as the frame ages
+                let color = (colors.input == ColorInput::Age)
+                    .then(|| colors.color(self.settings.color_mode, age as f32 / length));
+                (age, self.trails.frame(age), fade, color)
+            })
+            .collect();
+        let mut points = Vec::with_capacity(frames.len());
+        for (i, agent) in self.agents.iter().enumerate() {
+            points.clear();
+            points.extend(frames.iter().map(|(age, frame, fade, color)| {
+                let pos = match age {
+                    0 => agent.prev_pos.lerp(agent.pos, alpha),
+                    _ => frame.pos(i),
+                };
+                let color = color.unwrap_or_else(|| frame.color(i));
+                (
+                    pos,
+                    Color::new(color.red, color.green, color.blue, color.alpha * fade),
+                )
+            }));
+            painter.polyline(&points, 1.0);
         }
-        Key::Space => {
-            model.noise_seed = (random_f32() * 10000.0).floor() as u32;
-            model.perlin = Perlin::new().set_seed(model.noise_seed);
+        if draw_target {
+            self.targets
+                .iter()
+                .for_each(|target| painter.point(target.pos, 1.0, RED));
         }
-        Key::R => {
-            model.agents_history = VecDeque::new();
-            model.reset_agents();
+    }
+
+    // agents target points on the canvas that update according to each
+    // one's target mode; targets sharing a path are spread out along it
+    fn next_target(&self, index: usize, time: f32, mouse: Pos) -> Pos {
+        let target = self.targets[index];
+        // how far along its path a target starts, as a fraction of a loop
+        let phase = index as f32 / self.targets.len() as f32;
+        let curves = &self.settings.curves;
+        match target.mode {
+            TargetMode::Circle => curves.circle.at(time, phase),
+            TargetMode::FigureEight => curves.figure_eight.at(time, phase),
+            TargetMode::Lissajous => curves.lissajous.at(time, phase),
+            TargetMode::Rose => curves.rose.at(time, phase),
+            TargetMode::Spiral => curves.spiral.at(time, phase),
+            TargetMode::Spline => curves.spline.at(time, phase),
+            TargetMode::Svg => curves.svg.at(time, phase, self.win),
+            TargetMode::Noise => {
+                // each wanders through its own slice of the noise
+                let dir = self.field.direction([
+                    target.pos.x as f64 / self.noise_scale,
+                    target.pos.y as f64 / self.noise_scale,
+                    index as f64 * 100.0,
+                    time as f64,
+                ]);
+                let target = target.pos + dir * 30.0;
+                target.pow(0.99)
+            }
+            TargetMode::Average => {
+                // tracks the average of the agents chasing it, with an
+                // attraction factor to canvas center
+                let (sum, count) = self
+                    .agents
+                    .iter()
+                    .filter(|a| a.target == index)
+                    .fold((Pos::new(0.0, 0.0), 0), |(sum, count), a| {
+                        (sum + a.pos, count + 1)
+                    });
+                match count {
+                    0 => target.pos.pow(0.95),
+                    _ => (sum / count as f32).pow(0.95),
+                }
+            }
+            TargetMode::Mouse => {
+                // the current mouse position
+                mouse
+            }
         }
-        _other_key => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Canvas;
+
+    #[test]
+    fn paints_straight_after_gaining_agents() {
+        let mut canvas = Canvas::new(200, 200);
+        let settings = Settings {
+            agent_count: 5,
+            ..Default::default()
+        };
+        let mut sim = Sim::new(canvas.rect(), 1, settings.clone());
+        sim.update(0.0, 1.0 / 60.0, Pos::new(0.0, 0.0));
+        sim.apply_settings(Settings {
+            agent_count: 9,
+            ..settings
+        });
+        // as on a frame that takes no steps
+        sim.paint(&mut canvas, true, 0.5);
+        sim.update(1.0 / 60.0, 1.0 / 60.0, Pos::new(0.0, 0.0));
+        sim.paint(&mut canvas, true, 0.5);
+        assert_eq!(sim.trails.frame(0).xs.len(), 9);
     }
 }
@@ -1,103 +1,342 @@
-// Agents that move following a target, while being pushed around by Perlin noise.
+// A dense field of short-lived agents drifting through slowly evolving Perlin
+// noise, respawning somewhere random when their time runs out.
 
-use nannou::noise::{Perlin, Seedable};
-use nannou::prelude::*;
+use nannou::geom::Rect;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-pub mod agent;
-pub mod pos;
-use agent::Agent;
-use pos::Pos;
+use crate::accum::{self, Accumulator};
+use crate::agent::Agent;
+use crate::color::{self, Color, ColorMode};
+use crate::colormap;
+use crate::field::{self, FlowField};
+use crate::motion;
+use crate::painter::Painter;
+use crate::par;
+use crate::picture::{self, Guided};
+use crate::pos::Pos;
+use crate::rng::{self, SimRng};
 
-fn main() {
-    nannou::app(model).update(update).run();
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub agent_count: usize,
+    pub noise_scale: f64,
+    // the noise agents steer by
+    pub field: field::Settings,
+    pub step_size: f32, // in pixels per frame
+    // whether agents have inertia, and if so how heavy and damped they are
+    pub motion: motion::Settings,
+    // step size of agents once they've respawned
+    pub respawn_step_size: f32,
+    // fraction of the distance to the centre covered each step
+    pub target_accel: f32,
+    // without a colour mode, trails are coloured by remaining ttl:
+    // hue = hue_base + ttl * hue_per_ttl, in turns
+    pub hue_base: f32,
+    pub hue_per_ttl: f32,
+    // a colour mode from a palette instead, cycled with C, and the palettes
+    // and input behind it
+    pub color_mode: Option<ColorMode>,
+    pub colors: colormap::Settings,
+    pub line_weight: f32,
+    // opacity of the black drawn over each frame; lower leaves longer trails
+    pub fade_alpha: f32,
+    // a photograph for the agents to paint
+    pub picture: picture::Settings,
+    // long exposure, toggled with A: agents deposit light into a buffer
+    // that decays, instead of painting onto the window
+    pub accum: accum::Settings,
 }
 
-struct Model {
-    perlin: Perlin,
-    noise_seed: u32,
-    noise_scale: f64,
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            agent_count: 1000,
+            noise_scale: 800.0,
+            field: field::Settings::default(),
+            step_size: 20.0,
+            motion: motion::Settings::default(),
+            respawn_step_size: 2.0,
+            target_accel: 0.002,
+            hue_base: 0.5,
+            hue_per_ttl: 0.05,
+            color_mode: None,
+            colors: colormap::Settings::default(),
+            line_weight: 1.5,
+            fade_alpha: 0.01,
+            picture: picture::Settings::default(),
+            accum: accum::Settings::default(),
+        }
+    }
+}
+
+pub struct Sim {
+    pub field: Box<dyn FlowField>,
+    pub noise_seed: u32,
+    pub noise_scale: f64,
     pub agents: Vec<Agent>,
-    win: Rect,
+    pub win: Rect,
+    pub seed: u64,
+    pub settings: Settings,
+    // only while accumulating
+    accumulator: Option<Accumulator>,
+    // how far along each agent's path its trail has been painted, so
+    // painting can stop partway through a step and carry on from there
+    painted: Vec<Pos>,
+    rng: SimRng,
 }
 
-impl Model {
-    pub fn new(win: Rect) -> Self {
-        let agent_count = 1000;
-        let noise_scale = 800.0;
-        let noise_seed = random::<u32>();
-        let perlin = Perlin::new().set_seed(noise_seed);
-        let agents = (0..agent_count)
-            .map(|_| Agent::new(win, false, 20f32))
+impl Sim {
+    pub fn new(win: Rect, seed: u64, settings: Settings) -> Self {
+        let mut rng = rng::from_seed(seed);
+        let noise_scale = settings.noise_scale;
+        let noise_seed = rng.gen::<u32>();
+        let field = field::build(&settings.field, noise_seed);
+        let agents: Vec<Agent> = (0..settings.agent_count)
+            .map(|_| spawn(win, &settings, &mut rng))
             .collect();
+        let accumulator = settings.accum.enabled.then(|| Accumulator::new(win));
+        let painted = agents.iter().map(|a| a.pos).collect();
 
-        Model {
-            perlin,
+        Sim {
+            field,
             noise_seed,
             noise_scale,
             agents,
             win,
+            seed,
+            settings,
+            accumulator,
+            painted,
+            rng,
         }
     }
 
     pub fn reset_agents(&mut self) {
         self.agents = (0..self.agents.len())
-            .map(|_| Agent::new(self.win, false, 20f32))
+            .map(|_| spawn(self.win, &self.settings, &mut self.rng))
             .collect();
+        self.painted = self.agents_pos();
+        // the window may have changed size
+        if self.accumulator.is_some() {
+            self.accumulator = Some(Accumulator::new(self.win));
+        }
+    }
+
+    pub fn reseed(&mut self) {
+        self.noise_seed = self.rng.gen_range(0..10000);
+        self.field = field::build(&self.settings.field, self.noise_seed);
+    }
+
+    // switch to new settings on the fly, keeping as much of the current state
+    // as possible
+    pub fn apply_settings(&mut self, settings: Settings) {
+        self.noise_scale = settings.noise_scale;
+        if settings.field != self.settings.field {
+            self.field = field::build(&settings.field, self.noise_seed);
+        }
+        self.set_motion(settings.motion);
+        self.agents.truncate(settings.agent_count);
+        while self.agents.len() < settings.agent_count {
+            let agent = spawn(self.win, &settings, &mut self.rng);
+            self.agents.push(agent);
+        }
+        // agents still on their first life step at `step_size`, and those
+        // that have respawned at `respawn_step_size`
+        for a in self.agents.iter_mut() {
+            a.step_size = match a.respawned {
+                true => settings.respawn_step_size,
+                false => settings.step_size,
+            };
+        }
+        self.set_accum(settings.accum.clone());
+        self.settings = settings;
+    }
+
+    // switch to a different flow field, keeping the noise seed
+    pub fn set_field(&mut self, settings: field::Settings) {
+        self.field = field::build(&settings, self.noise_seed);
+        self.settings.field = settings;
+    }
+
+    // switch agents between stepping and inertia, or change how they move
+    // with it
+    pub fn set_motion(&mut self, motion: motion::Settings) {
+        self.agents.iter_mut().for_each(|a| a.motion = motion);
+        self.settings.motion = motion;
+    }
+
+    // start or stop accumulating, or change how
+    pub fn set_accum(&mut self, settings: accum::Settings) {
+        match (settings.enabled, self.accumulator.is_some()) {
+            (true, false) => self.accumulator = Some(Accumulator::new(self.win)),
+            (false, true) => {
+                // trails pick up from where agents are now, not from where
+                // they were when accumulating started
+                self.accumulator = None;
+                self.painted = self.agents_pos();
+            }
+            _ => {}
+        }
+        self.settings.accum = settings;
+    }
+
+    // the long exposure so far, if accumulating
+    pub fn accumulator(&self) -> Option<&Accumulator> {
+        self.accumulator.as_ref()
     }
 
-    pub fn _agents_pos(&self) -> Vec<Pos> {
+    pub fn agents_pos(&self) -> Vec<Pos> {
         self.agents.iter().map(|agent| agent.pos).collect()
     }
-}
 
-fn model(app: &App) -> Model {
-    app.new_window()
-        .size(1000, 1000)
-        .view(view)
-        .key_released(key_released)
-        .build()
-        .unwrap();
-    Model::new(app.window_rect())
+    // advance one frame; `time` is seconds since start, `dt` seconds since the
+    // previous update
+    pub fn update(&mut self, time: f32, dt: f32) {
+        let (noise_scale, win) = (self.noise_scale, self.win);
+        let respawn_step_size = self.settings.respawn_step_size;
+        let target_accel = self.settings.target_accel;
+        let picture = &self.settings.picture;
+        let guided = picture.picture().map(|_| Guided {
+            field: &*self.field,
+            settings: picture,
+            noise_scale,
+            win,
+        });
+        let field: &dyn FlowField = match &guided {
+            Some(guided) => guided,
+            None => &*self.field,
+        };
+        par::for_each_mut(&mut self.agents, |a| {
+            if a.expired() && guided.is_some() {
+                a.respawn_weighted(win, respawn_step_size, |p| picture.spawn_chance(p, win));
+            } else if a.expired() {
+                a.respawn(win, respawn_step_size);
+            } else {
+                let target = Pos::new(0.0, 0.0);
+                let pace = match guided {
+                    Some(_) => picture.speed_at(a.pos, win),
+                    None => 1.0,
+                };
+                a.update2(field, noise_scale, target, target_accel, pace, time, dt);
+            }
+        });
+
+        if let Some(accumulator) = &mut self.accumulator {
+            let s = &self.settings;
+            accumulator.decay(s.accum.decay, dt);
+            accumulator.blur(s.accum.blur);
+            // skipping those that have just respawned
+            for agent in self.agents.iter().filter(|a| a.prev_pos != a.pos) {
+                let color = trail_color(s, agent, win);
+                accumulator.deposit_line(agent.prev_pos, agent.pos, color, s.accum.deposit);
+            }
+        }
+    }
+
+    // Fade out what's been painted a little, then paint each agent's trail
+    // on up to where its latest step started. Called after every update, so
+    // trails fade at the same rate whatever the frame rate.
+    pub fn paint_step<P: Painter>(&mut self, painter: &mut P) {
+        let s = &self.settings;
+        painter.fade(Color::new(0.0, 0.0, 0.0, s.fade_alpha));
+        self.paint(painter, 0.0);
+    }
+
+    // Paint each agent's trail on up to `alpha` of the way through its latest
+    // step, from 0 (where it started) to 1 (where it ended), so that frames
+    // drawn between updates still show agents moving smoothly. Called before
+    // each frame is shown.
+    pub fn paint<P: Painter>(&mut self, painter: &mut P, alpha: f32) {
+        // agents have been added, removed or replaced since
+        if self.painted.len() != self.agents.len() {
+            self.painted = self.agents_pos();
+        }
+        let s = &self.settings;
+        for (painted, agent) in self.painted.iter_mut().zip(&self.agents) {
+            // just respawned, so starting a new trail
+            if agent.prev_pos == agent.pos {
+                *painted = agent.pos;
+                continue;
+            }
+            let to = agent.prev_pos.lerp(agent.pos, alpha);
+            let color = trail_color(s, agent, self.win);
+            painter.line(*painted, to, s.line_weight, color);
+            *painted = to;
+        }
+    }
 }
 
-fn update(app: &App, model: &mut Model, _update: Update) {
-    model.agents.iter_mut().for_each(|a| {
-        a.update2(
-            model.perlin,
-            model.noise_scale,
-            Pos::new(0.0, 0.0),
-            model.win,
-            app.duration,
-        )
-    });
+// a new agent somewhere in `win`, more likely in the bright if there's a
+// picture, as agents respawn
+fn spawn(win: Rect, settings: &Settings, rng: &mut SimRng) -> Agent {
+    let (step_size, picture) = (settings.step_size, &settings.picture);
+    let mut agent = Agent::new(win, false, step_size, settings.motion, rng);
+    if picture.picture().is_some() {
+        agent.respawn_weighted(win, step_size, |p| picture.spawn_chance(p, win));
+        // placed as a respawn would be, but still on its first life
+        agent.respawned = false;
+    }
+    agent
 }
 
-fn view(app: &App, model: &Model, frame: Frame) {
-    let draw = app.draw();
-    draw.rect()
-        .wh(app.window_rect().wh())
-        .hsva(0.0, 0.0, 0.0, 0.01);
-    // draw the newest agent set
-    model.agents.iter().for_each(|agent| {
-        draw.line()
-            .start(Vec2::new(agent.prev_pos.x, agent.prev_pos.y))
-            .end(Vec2::new(agent.pos.x, agent.pos.y))
-            .weight(1.5)
-            .hsv(0.5 + agent.ttl / 20.0, 1.0, 1.0);
-    });
-    draw.to_frame(&app, &frame).unwrap();
+// the colour of an agent's latest step: from the palette if there's a colour
+// mode, and otherwise its hue by remaining ttl, tinted by the picture if
+// there is one
+fn trail_color(settings: &Settings, agent: &Agent, win: Rect) -> Color {
+    let color = match settings.color_mode {
+        Some(mode) => {
+            let colors = &settings.colors;
+            // how far through its life it is
+            let age = agent.age / (agent.age + agent.ttl.max(0.0)).max(1e-6);
+            let target = Pos::new(0.0, 0.0);
+            colors.color(mode, colors.position(agent, age, target, win))
+        }
+        None => color::hsv(
+            settings.hue_base + agent.ttl * settings.hue_per_ttl,
+            1.0,
+            1.0,
+        ),
+    };
+    settings.picture.tint(color, agent.pos, win)
 }
 
-fn key_released(app: &App, model: &mut Model, key: Key) {
-    match key {
-        Key::Space => {
-            model.noise_seed = (random_f32() * 10000.0).floor() as u32;
-            model.perlin = Perlin::new().set_seed(model.noise_seed);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn live_edits_keep_first_lives_and_respawns_apart() {
+        // the same step size for both, so it can't tell them apart
+        let settings = Settings {
+            agent_count: 200,
+            step_size: 4.0,
+            respawn_step_size: 4.0,
+            ..Default::default()
+        };
+        let mut sim = Sim::new(Rect::from_w_h(400.0, 400.0), 2, settings.clone());
+        // long enough for some, but not all, to respawn
+        for step in 0..300 {
+            sim.update(step as f32 / 60.0, 1.0 / 60.0);
         }
-        Key::R => {
-            model.win = app.window_rect();
-            model.reset_agents();
+        let respawned = sim.agents.iter().filter(|a| a.respawned).count();
+        assert!(respawned > 0 && respawned < sim.agents.len());
+        // and twice over, to be sure one edit doesn't throw off the next
+        for (step_size, respawn_step_size) in [(6.0, 3.0), (5.0, 2.0)] {
+            sim.apply_settings(Settings {
+                step_size,
+                respawn_step_size,
+                ..settings.clone()
+            });
+            for a in &sim.agents {
+                let expected = if a.respawned {
+                    respawn_step_size
+                } else {
+                    step_size
+                };
+                assert_eq!(a.step_size, expected);
+            }
         }
-        _other_key => {}
     }
 }
@@ -0,0 +1,322 @@
+// A photograph for agents to paint: laid over the window, its brightness and
+// edges steer them, pace them, decide where they respawn and tint their
+// trails, so the picture builds up out of the flow.
+
+use std::f32::consts::PI;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use nannou::geom::Rect;
+use serde::{Deserialize, Serialize};
+
+use crate::color::{self, Color};
+use crate::field::FlowField;
+use crate::pos::Pos;
+use crate::preset::Error;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PictureFlow {
+    // brightness as an angle, as the noise is
+    Luminance,
+    // along the edges, at right angles to the brightness gradient, so agents
+    // trace outlines
+    Gradient,
+}
+
+// How strongly the picture drives each part of the sketch, from 0 (not at
+// all) to 1 (entirely). Nothing changes without a `file`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    // a PNG or JPEG, fit inside the window
+    pub file: Option<PathBuf>,
+    // use darkness instead of brightness, for dark subjects on light
+    // backgrounds
+    pub invert: bool,
+    // how the picture turns into a direction, and how much of the flow that
+    // direction makes up
+    pub flow_mode: PictureFlow,
+    pub flow: f32,
+    // slow agents down in the dark
+    pub speed: f32,
+    // respawn agents in the bright more than the dark
+    pub density: f32,
+    // trails take the picture's colour rather than their hue
+    pub color: f32,
+    // the decoded picture; filled in from `file` by `load`
+    #[serde(skip)]
+    picture: Option<Arc<Picture>>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            file: None,
+            invert: false,
+            flow_mode: PictureFlow::Gradient,
+            flow: 0.5,
+            speed: 0.5,
+            density: 1.0,
+            color: 1.0,
+            picture: None,
+        }
+    }
+}
+
+impl Settings {
+    // decode `file`, if there is one
+    pub fn load(&mut self) -> Result<(), Error> {
+        self.picture = match &self.file {
+            Some(path) => Some(Arc::new(Picture::open(path)?)),
+            None => None,
+        };
+        Ok(())
+    }
+
+    pub fn picture(&self) -> Option<&Picture> {
+        self.picture.as_deref()
+    }
+
+    // the picture under `pos` in a window `win`, with `invert` applied;
+    // nothing off the edges of the picture or without one
+    pub fn sample(&self, pos: Pos, win: Rect) -> Option<Sample> {
+        let mut sample = self.picture()?.sample(pos, win)?;
+        if self.invert {
+            sample.luminance = 1.0 - sample.luminance;
+            sample.gradient = sample.gradient * -1.0;
+        }
+        Some(sample)
+    }
+
+    // how fast agents move at `pos`, as a fraction of full speed: slower in
+    // the dark, and full speed off the picture or without one
+    pub fn speed_at(&self, pos: Pos, win: Rect) -> f32 {
+        match self.sample(pos, win) {
+            Some(sample) => 1.0 - self.speed * (1.0 - sample.luminance),
+            None => 1.0,
+        }
+    }
+
+    // the chance of keeping a respawn at `pos`
+    pub fn spawn_chance(&self, pos: Pos, win: Rect) -> f32 {
+        let luminance = self.sample(pos, win).map_or(0.0, |s| s.luminance);
+        1.0 - self.density * (1.0 - luminance)
+    }
+
+    // `base` tinted towards the picture's colour under `pos`
+    pub fn tint(&self, base: Color, pos: Pos, win: Rect) -> Color {
+        match self.sample(pos, win) {
+            Some(sample) => {
+                let k = self.color;
+                Color::new(
+                    base.red + (sample.color.red - base.red) * k,
+                    base.green + (sample.color.green - base.green) * k,
+                    base.blue + (sample.color.blue - base.blue) * k,
+                    base.alpha,
+                )
+            }
+            None => base,
+        }
+    }
+}
+
+// The picture under a point.
+#[derive(Copy, Clone, Debug)]
+pub struct Sample {
+    // 0 (black) to 1 (white)
+    pub luminance: f32,
+    // which way luminance increases fastest, y up, in luminance per pixel of
+    // the picture
+    pub gradient: Pos,
+    pub color: Color,
+}
+
+pub struct Picture {
+    width: u32,
+    height: u32,
+    // row-major, top row first
+    samples: Vec<Sample>,
+}
+
+impl fmt::Debug for Picture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Picture({}x{})", self.width, self.height)
+    }
+}
+
+impl Picture {
+    pub fn open(path: &Path) -> Result<Picture, Error> {
+        let image = image::open(path)
+            .map_err(|e| Error::Invalid(format!("couldn't load {}: {}", path.display(), e)))?
+            .to_rgb8();
+        let (width, height) = image.dimensions();
+        let colors: Vec<Color> = image
+            .pixels()
+            .map(|p| color::rgb8(p[0], p[1], p[2]))
+            .collect();
+        let luminance: Vec<f32> = colors
+            .iter()
+            .map(|c| 0.2126 * c.red + 0.7152 * c.green + 0.0722 * c.blue)
+            .collect();
+        // Sobel, with the edges repeated outwards
+        let at = |x: i64, y: i64| {
+            let x = x.clamp(0, width as i64 - 1);
+            let y = y.clamp(0, height as i64 - 1);
+            luminance[(y * width as i64 + x) as usize]
+        };
+        let samples = (0..height as i64)
+            .flat_map(|y| (0..width as i64).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let gx = (at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1))
+                    - (at(x - 1, y - 1) + 2.0 * at(x - 1, y) + at(x - 1, y + 1));
+                let gy = (at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1))
+                    - (at(x - 1, y - 1) + 2.0 * at(x, y - 1) + at(x + 1, y - 1));
+                let i = (y * width as i64 + x) as usize;
+                Sample {
+                    luminance: luminance[i],
+                    // the image's rows run downwards
+                    gradient: Pos::new(gx, -gy) / 8.0,
+                    color: colors[i],
+                }
+            })
+            .collect();
+        Ok(Picture {
+            width,
+            height,
+            samples,
+        })
+    }
+
+    // the pixel under `pos`, with the picture scaled to fit inside `win` and
+    // centred in it
+    pub fn sample(&self, pos: Pos, win: Rect) -> Option<Sample> {
+        let (w, h) = (self.width as f32, self.height as f32);
+        let scale = (win.w() / w).min(win.h() / h);
+        let x = (pos.x - win.x()) / scale + w / 2.0;
+        let y = (win.y() - pos.y) / scale + h / 2.0;
+        if !(x >= 0.0 && x < w && y >= 0.0 && y < h) {
+            return None;
+        }
+        Some(self.samples[y as usize * self.width as usize + x as usize])
+    }
+}
+
+// A flow field steered by a picture as well as noise: its direction blended
+// with the noise's, still a unit vector. The field is
+// looked up in noise space, so points are scaled back up by `noise_scale`
+// to find where they are in the window.
+pub struct Guided<'a> {
+    pub field: &'a dyn FlowField,
+    pub settings: &'a Settings,
+    pub noise_scale: f64,
+    pub win: Rect,
+}
+
+impl FlowField for Guided<'_> {
+    fn direction(&self, p: [f64; 4]) -> Pos {
+        let dir = self.field.direction(p);
+        let pos = Pos::new(
+            (p[0] * self.noise_scale) as f32,
+            (p[1] * self.noise_scale) as f32,
+        );
+        let Some(sample) = self.settings.sample(pos, self.win) else {
+            return dir;
+        };
+        let s = self.settings;
+        let guide = match s.flow_mode {
+            PictureFlow::Luminance => {
+                let theta = sample.luminance * 2.0 * PI;
+                Pos::new(theta.cos(), theta.sin())
+            }
+            // no edge to follow on flat patches
+            PictureFlow::Gradient => match sample.gradient.magnitude() {
+                m if m > 1e-3 => Pos::new(-sample.gradient.y, sample.gradient.x) / m,
+                _ => dir,
+            },
+        };
+        let blend = dir * (1.0 - s.flow) + guide * s.flow;
+        match blend.magnitude() {
+            length if length > 1e-6 => blend / length,
+            _ => dir,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // always the same way
+    struct Fixed(Pos);
+
+    impl FlowField for Fixed {
+        fn direction(&self, _: [f64; 4]) -> Pos {
+            self.0
+        }
+    }
+
+    // a 2x2 picture, black down the left and grey down the right, fit into
+    // a 200x200 window
+    fn settings(flow_mode: PictureFlow) -> Settings {
+        let sample = |luminance, gradient| Sample {
+            luminance,
+            gradient,
+            color: Color::new(luminance, luminance, luminance, 1.0),
+        };
+        let (dark, grey) = (
+            sample(0.0, Pos::new(0.25, 0.0)),
+            sample(0.5, Pos::new(0.25, 0.0)),
+        );
+        let picture = Picture {
+            width: 2,
+            height: 2,
+            samples: vec![dark, grey, dark, grey],
+        };
+        Settings {
+            flow_mode,
+            picture: Some(Arc::new(picture)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn guided_directions_are_unit_length() {
+        let win = Rect::from_w_h(200.0, 200.0);
+        let field = Fixed(Pos::new(0.6, 0.8));
+        for mode in [PictureFlow::Luminance, PictureFlow::Gradient] {
+            let settings = settings(mode);
+            let guided = Guided {
+                field: &field,
+                settings: &settings,
+                noise_scale: 100.0,
+                win,
+            };
+            for p in [
+                [-0.5, 0.5, 0.0, 0.0],
+                [0.5, -0.5, 0.0, 0.0],
+                [5.0, 5.0, 0.0, 0.0],
+            ] {
+                let length = guided.direction(p).magnitude();
+                assert!(
+                    (length - 1.0).abs() < 1e-5,
+                    "{:?} at {:?}: {}",
+                    mode,
+                    p,
+                    length
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn slower_in_the_dark() {
+        let win = Rect::from_w_h(200.0, 200.0);
+        let settings = settings(PictureFlow::Gradient);
+        assert_eq!(settings.speed_at(Pos::new(-50.0, 0.0), win), 0.5);
+        assert_eq!(settings.speed_at(Pos::new(50.0, 0.0), win), 0.75);
+        // full speed off the picture
+        assert_eq!(settings.speed_at(Pos::new(500.0, 0.0), win), 1.0);
+    }
+}
@@ -0,0 +1,389 @@
+// Pen-plotter output: recording agent trails as polylines, then simplifying,
+// ordering and writing them out for plotters.
+
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use nannou::geom::{Rect, Vec2};
+
+use crate::agent::Agent;
+use crate::pos::Pos;
+use crate::svg::Svg;
+
+// Collects each agent's `prev_pos -> pos` steps into one continuous polyline
+// per agent lifetime. A step that doesn't continue from where the agent's
+// polyline left off (a respawn, or a reset) starts a new polyline, as does
+// leaving `bounds`, so nothing is plotted off the page.
+pub struct Recorder {
+    bounds: Rect,
+    open: Vec<Vec<Pos>>,
+    done: Vec<Vec<Pos>>,
+}
+
+impl Recorder {
+    pub fn new(bounds: Rect) -> Self {
+        Recorder {
+            bounds,
+            open: Vec::new(),
+            done: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, agents: &[Agent]) {
+        if self.open.len() != agents.len() {
+            // agents were added or replaced wholesale; start everything afresh
+            self.done.append(&mut self.open);
+            self.open = vec![Vec::new(); agents.len()];
+        }
+        let inside = |pos: Pos| self.bounds.contains(Vec2::new(pos.x, pos.y));
+        for (agent, path) in agents.iter().zip(self.open.iter_mut()) {
+            if path.last() != Some(&agent.prev_pos) {
+                close(path, &mut self.done);
+            }
+            if !inside(agent.pos) {
+                close(path, &mut self.done);
+                continue;
+            }
+            if path.is_empty() && inside(agent.prev_pos) {
+                path.push(agent.prev_pos);
+            }
+            path.push(agent.pos);
+        }
+    }
+
+    pub fn finish(mut self) -> Plot {
+        self.done.append(&mut self.open);
+        let paths = self.done.into_iter().filter(|p| p.len() >= 2).collect();
+        Plot {
+            bounds: self.bounds,
+            paths,
+        }
+    }
+}
+
+fn close(path: &mut Vec<Pos>, done: &mut Vec<Vec<Pos>>) {
+    if !path.is_empty() {
+        done.push(std::mem::take(path));
+    }
+}
+
+// Physical output settings for HPGL and G-code. The recorded bounds are scaled
+// to fit inside the paper's margins, keeping their aspect ratio.
+#[derive(Copy, Clone, Debug)]
+pub struct PlotSettings {
+    pub tolerance: f32,   // simplification tolerance, in pixels
+    pub paper_width: f32, // in mm
+    pub paper_height: f32,
+    pub margin: f32,
+    pub pen_up_z: f32, // G-code only
+    pub pen_down_z: f32,
+    pub feed_rate: f32, // G-code only, mm/min
+}
+
+impl Default for PlotSettings {
+    // A4 portrait
+    fn default() -> Self {
+        PlotSettings {
+            tolerance: 0.5,
+            paper_width: 210.0,
+            paper_height: 297.0,
+            margin: 15.0,
+            pen_up_z: 5.0,
+            pen_down_z: 0.0,
+            feed_rate: 3000.0,
+        }
+    }
+}
+
+impl PlotSettings {
+    // window coordinates to paper mm, origin bottom-left and y up
+    fn paper_pos(&self, bounds: Rect, pos: Pos) -> Pos {
+        let avail_w = self.paper_width - 2.0 * self.margin;
+        let avail_h = self.paper_height - 2.0 * self.margin;
+        let scale = (avail_w / bounds.w()).min(avail_h / bounds.h());
+        let centre = Pos::new(self.paper_width / 2.0, self.paper_height / 2.0);
+        centre + (pos - Pos::new(bounds.x(), bounds.y())) * scale
+    }
+}
+
+pub struct Plot {
+    pub bounds: Rect,
+    pub paths: Vec<Vec<Pos>>,
+}
+
+impl Plot {
+    // simplify then reorder, ready for plotting
+    pub fn prepare(&mut self, settings: &PlotSettings) {
+        self.simplify(settings.tolerance);
+        self.optimize_order();
+    }
+
+    // Ramer-Douglas-Peucker simplification of every path; `epsilon` is the
+    // furthest a dropped point may lie from the simplified path, in pixels
+    pub fn simplify(&mut self, epsilon: f32) {
+        for path in self.paths.iter_mut() {
+            *path = simplify(path, epsilon);
+        }
+    }
+
+    // reorder (and reverse where it helps) the paths, greedily picking the
+    // nearest path end each time, to cut down on pen-up travel
+    pub fn optimize_order(&mut self) {
+        let mut remaining = std::mem::take(&mut self.paths);
+        let mut pen = Pos::new(self.bounds.left(), self.bounds.bottom());
+        while !remaining.is_empty() {
+            let mut best = (0, false, f32::MAX);
+            for (i, path) in remaining.iter().enumerate() {
+                let to_start = (path[0] - pen).magnitude();
+                let to_end = (path[path.len() - 1] - pen).magnitude();
+                if to_start < best.2 {
+                    best = (i, false, to_start);
+                }
+                if to_end < best.2 {
+                    best = (i, true, to_end);
+                }
+            }
+            let mut path = remaining.swap_remove(best.0);
+            if best.1 {
+                path.reverse();
+            }
+            pen = path[path.len() - 1];
+            self.paths.push(path);
+        }
+    }
+
+    // total distance travelled with the pen up, in pixels
+    pub fn pen_up_distance(&self) -> f32 {
+        let mut pen = Pos::new(self.bounds.left(), self.bounds.bottom());
+        let mut total = 0.0;
+        for path in self.paths.iter() {
+            total += (path[0] - pen).magnitude();
+            pen = path[path.len() - 1];
+        }
+        total
+    }
+
+    pub fn to_svg(&self) -> Svg {
+        let mut svg = Svg::new(self.bounds);
+        for path in self.paths.iter() {
+            svg.polyline(path, "fill:none;stroke:black;stroke-width:1");
+        }
+        svg
+    }
+
+    pub fn to_hpgl(&self, settings: &PlotSettings) -> String {
+        // HPGL plotter units are 0.025mm
+        let unit = |pos: Pos| {
+            let mm = settings.paper_pos(self.bounds, pos);
+            ((mm.x * 40.0).round() as i32, (mm.y * 40.0).round() as i32)
+        };
+        let mut out = String::from("IN;SP1;\n");
+        for path in self.paths.iter() {
+            let (x, y) = unit(path[0]);
+            let _ = writeln!(out, "PU{},{};", x, y);
+            let coords: Vec<String> = path[1..]
+                .iter()
+                .map(|p| {
+                    let (x, y) = unit(*p);
+                    format!("{},{}", x, y)
+                })
+                .collect();
+            let _ = writeln!(out, "PD{};", coords.join(","));
+        }
+        out.push_str("PU;SP0;\n");
+        out
+    }
+
+    pub fn to_gcode(&self, settings: &PlotSettings) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "G21 ; millimetres");
+        let _ = writeln!(out, "G90 ; absolute positioning");
+        let _ = writeln!(out, "G0 Z{:.2}", settings.pen_up_z);
+        for path in self.paths.iter() {
+            let start = settings.paper_pos(self.bounds, path[0]);
+            let _ = writeln!(out, "G0 X{:.3} Y{:.3}", start.x, start.y);
+            let _ = writeln!(
+                out,
+                "G1 Z{:.2} F{:.0}",
+                settings.pen_down_z, settings.feed_rate
+            );
+            for pos in path[1..].iter() {
+                let p = settings.paper_pos(self.bounds, *pos);
+                let _ = writeln!(out, "G1 X{:.3} Y{:.3}", p.x, p.y);
+            }
+            let _ = writeln!(out, "G0 Z{:.2}", settings.pen_up_z);
+        }
+        let _ = writeln!(out, "G0 X0 Y0");
+        let _ = writeln!(out, "M2");
+        out
+    }
+
+    // write `<stem>.svg`, `<stem>.hpgl` and `<stem>.gcode`
+    pub fn save_all<P: AsRef<Path>>(&self, stem: P, settings: &PlotSettings) -> io::Result<()> {
+        let stem = stem.as_ref();
+        self.to_svg().save(stem.with_extension("svg"))?;
+        fs::write(stem.with_extension("hpgl"), self.to_hpgl(settings))?;
+        fs::write(stem.with_extension("gcode"), self.to_gcode(settings))?;
+        Ok(())
+    }
+}
+
+pub fn simplify(points: &[Pos], epsilon: f32) -> Vec<Pos> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    // iterative rather than recursive, so long trails can't blow the stack
+    let mut stack = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = stack.pop() {
+        let mut furthest = (first, 0.0);
+        for i in first + 1..last {
+            let d = line_distance(points[i], points[first], points[last]);
+            if d > furthest.1 {
+                furthest = (i, d);
+            }
+        }
+        if furthest.1 > epsilon {
+            keep[furthest.0] = true;
+            stack.push((first, furthest.0));
+            stack.push((furthest.0, last));
+        }
+    }
+    points
+        .iter()
+        .zip(keep)
+        .filter_map(|(p, k)| k.then_some(*p))
+        .collect()
+}
+
+// perpendicular distance from `p` to the line through `a` and `b`
+fn line_distance(p: Pos, a: Pos, b: Pos) -> f32 {
+    let ab = b - a;
+    let len = ab.magnitude();
+    if len == 0.0 {
+        return (p - a).magnitude();
+    }
+    ((p.x - a.x) * ab.y - (p.y - a.y) * ab.x).abs() / len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(x: f32, y: f32) -> Pos {
+        Pos::new(x, y)
+    }
+
+    // how far `q` is from the nearest segment of `line`
+    fn distance_to(q: Pos, line: &[Pos]) -> f32 {
+        line.windows(2)
+            .map(|w| {
+                let ab = w[1] - w[0];
+                let len2 = ab.x * ab.x + ab.y * ab.y;
+                let t = ((q.x - w[0].x) * ab.x + (q.y - w[0].y) * ab.y) / len2;
+                (q - (w[0] + ab * t.clamp(0.0, 1.0))).magnitude()
+            })
+            .fold(f32::MAX, f32::min)
+    }
+
+    #[test]
+    fn straight_runs_come_down_to_their_ends() {
+        let line: Vec<Pos> = (0..10).map(|i| p(i as f32, i as f32 * 2.0)).collect();
+        assert_eq!(simplify(&line, 0.01), [p(0.0, 0.0), p(9.0, 18.0)]);
+        // too short to simplify
+        assert_eq!(simplify(&line[..2], 0.01), &line[..2]);
+    }
+
+    #[test]
+    fn bumps_stay_only_past_the_tolerance() {
+        let bump = [
+            p(0.0, 0.0),
+            p(5.0, 0.3),
+            p(10.0, 0.0),
+            p(15.0, 2.0),
+            p(20.0, 0.0),
+        ];
+        assert_eq!(
+            simplify(&bump, 0.5),
+            [p(0.0, 0.0), p(10.0, 0.0), p(15.0, 2.0), p(20.0, 0.0)]
+        );
+        assert_eq!(simplify(&bump, 0.1), bump);
+        assert_eq!(simplify(&bump, 5.0), [p(0.0, 0.0), p(20.0, 0.0)]);
+    }
+
+    #[test]
+    fn dropped_points_lie_within_the_tolerance() {
+        let wave: Vec<Pos> = (0..500)
+            .map(|i| {
+                let x = i as f32 * 0.2;
+                p(x, 10.0 * (x / 7.0).sin() + (x * 3.1).sin() * 0.3)
+            })
+            .collect();
+        for epsilon in [0.1, 0.5, 2.0] {
+            let simple = simplify(&wave, epsilon);
+            assert!(simple.len() < wave.len());
+            assert_eq!((simple[0], simple[simple.len() - 1]), (wave[0], wave[499]));
+            for q in &wave {
+                assert!(distance_to(*q, &simple) <= epsilon + 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn loops_keep_their_corners() {
+        let square = [
+            p(0.0, 0.0),
+            p(10.0, 0.0),
+            p(10.0, 10.0),
+            p(0.0, 10.0),
+            p(0.0, 0.0),
+        ];
+        assert_eq!(simplify(&square, 0.5), square);
+    }
+
+    #[test]
+    fn greedy_order_takes_the_nearest_end_next() {
+        // the pen starts in the bottom left corner
+        let bounds = Rect::from_x_y_w_h(50.0, 50.0, 100.0, 100.0);
+        let far = vec![p(90.0, 90.0), p(90.0, 50.0)];
+        let near = vec![p(10.0, 0.0), p(50.0, 0.0)];
+        // its far end is nearer the end of `near`
+        let middle = vec![p(50.0, 60.0), p(55.0, 10.0)];
+        let mut plot = Plot {
+            bounds,
+            paths: vec![far.clone(), near.clone(), middle.clone()],
+        };
+        let before = plot.pen_up_distance();
+        plot.optimize_order();
+        let reversed = |path: &Vec<Pos>| path.iter().rev().copied().collect::<Vec<_>>();
+        assert_eq!(plot.paths, [near, reversed(&middle), reversed(&far)]);
+        assert!(plot.pen_up_distance() < before);
+    }
+
+    #[test]
+    fn ordering_keeps_every_path() {
+        let bounds = Rect::from_w_h(200.0, 200.0);
+        let paths: Vec<Vec<Pos>> = (0..40)
+            .map(|i| {
+                let (x, y) = ((i * 37 % 200) as f32 - 100.0, (i * 71 % 200) as f32 - 100.0);
+                vec![p(x, y), p(x + 5.0, y + (i % 3) as f32), p(x + 9.0, y - 4.0)]
+            })
+            .collect();
+        let mut plot = Plot {
+            bounds,
+            paths: paths.clone(),
+        };
+        let before = plot.pen_up_distance();
+        plot.optimize_order();
+        assert!(plot.pen_up_distance() < before);
+        assert_eq!(plot.paths.len(), paths.len());
+        for path in &paths {
+            let reversed: Vec<Pos> = path.iter().rev().copied().collect();
+            assert!(plot.paths.contains(path) || plot.paths.contains(&reversed));
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use nannou::math::num_traits::Pow;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Pos {
     pub x: f32,
     pub y: f32,
@@ -15,6 +15,11 @@ impl Pos {
         (self.x * self.x + self.y * self.y).sqrt()
     }
 
+    // the point `t` of the way from here to `to`
+    pub fn lerp(self, to: Pos, t: f32) -> Pos {
+        self + (to - self) * t
+    }
+
     pub fn pow(&self, rhs: f32) -> Pos {
         Pos::new(
             self.x.signum() * self.x.abs().pow(rhs),
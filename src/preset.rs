@@ -0,0 +1,82 @@
+// Preset files: a visualiser's settings saved as TOML or RON (picked by file
+// extension), optionally watched so edits can be re-applied while the sketch
+// runs.
+//
+// Presets can leave out any setting, which then keeps its default.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::de::DeserializeOwned;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Toml(PathBuf, toml::de::Error),
+    Ron(PathBuf, ron::error::SpannedError),
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "couldn't read {}: {}", path.display(), e),
+            Error::Toml(path, e) => write!(f, "couldn't parse {}: {}", path.display(), e),
+            Error::Ron(path, e) => write!(f, "couldn't parse {}: {}", path.display(), e),
+            Error::Invalid(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T, Error> {
+    let text = fs::read_to_string(path).map_err(|e| Error::Io(path.to_owned(), e))?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("ron") => ron::from_str(&text).map_err(|e| Error::Ron(path.to_owned(), e)),
+        _ => toml::from_str(&text).map_err(|e| Error::Toml(path.to_owned(), e)),
+    }
+}
+
+// Watches a preset file for changes. The whole directory is watched rather
+// than the file itself, since many editors save by replacing the file.
+pub struct PresetWatcher {
+    path: PathBuf,
+    events: Receiver<notify::Result<Event>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl PresetWatcher {
+    pub fn new(path: &Path) -> notify::Result<Self> {
+        let path = path.canonicalize()?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let (tx, events) = channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(PresetWatcher {
+            path,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // whether the file has been written since the last call; never blocks
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        while let Ok(event) = self.events.try_recv() {
+            if let Ok(event) = event {
+                let relevant = event.kind.is_modify() || event.kind.is_create();
+                changed |= relevant && event.paths.contains(&self.path);
+            }
+        }
+        changed
+    }
+}
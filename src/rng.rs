@@ -0,0 +1,83 @@
+// Seedable random number generation, so that a run can be reproduced exactly
+// from its seed.
+//
+// Each simulation owns a `SimRng` seeded once at startup, and every agent
+// splits its own stream off it at spawn. Agents only ever draw from their own
+// stream, so their trajectories don't depend on update order.
+
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+
+use crate::preset::Error;
+
+pub type SimRng = Pcg64Mcg;
+
+pub fn from_seed(seed: u64) -> SimRng {
+    SimRng::seed_from_u64(seed)
+}
+
+// a new, independent stream derived from `rng`
+pub fn split(rng: &mut SimRng) -> SimRng {
+    SimRng::seed_from_u64(rng.gen())
+}
+
+// the seed from the DUSTSTORM_SEED environment variable, or a random one if
+// it's unset
+pub fn seed_from_env() -> Result<u64, Error> {
+    match std::env::var("DUSTSTORM_SEED") {
+        Ok(seed) => seed.trim().parse().map_err(|_| {
+            Error::Invalid(format!(
+                "DUSTSTORM_SEED should be an unsigned integer, got '{}'",
+                seed
+            ))
+        }),
+        Err(_) => Ok(rand::random()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nannou::geom::Rect;
+
+    use crate::pos::Pos;
+    use crate::{perlin, perlin2, voronoi};
+
+    const STEPS: usize = 100;
+    const DT: f32 = 1.0 / 60.0;
+
+    fn run_perlin(seed: u64) -> Vec<Pos> {
+        let mut sim = perlin::Sim::new(Rect::from_w_h(800.0, 600.0), seed, Default::default());
+        for step in 0..STEPS {
+            sim.update(step as f32 * DT, DT, Pos::new(0.0, 0.0));
+        }
+        sim.agents_pos()
+    }
+
+    fn run_perlin2(seed: u64) -> Vec<Pos> {
+        let mut sim = perlin2::Sim::new(Rect::from_w_h(800.0, 600.0), seed, Default::default());
+        for step in 0..STEPS {
+            sim.update(step as f32 * DT, DT);
+        }
+        sim.agents_pos()
+    }
+
+    fn run_voronoi(seed: u64) -> Vec<Pos> {
+        let mut sim = voronoi::Sim::new(Rect::from_w_h(800.0, 600.0), seed, Default::default());
+        (0..STEPS).for_each(|_| sim.update());
+        sim.get_sites()
+    }
+
+    #[test]
+    fn same_seed_same_run() {
+        assert_eq!(run_perlin(7), run_perlin(7));
+        assert_eq!(run_perlin2(7), run_perlin2(7));
+        assert_eq!(run_voronoi(7), run_voronoi(7));
+    }
+
+    #[test]
+    fn different_seeds_differ() {
+        assert_ne!(run_perlin(7), run_perlin(8));
+        assert_ne!(run_perlin2(7), run_perlin2(8));
+        assert_ne!(run_voronoi(7), run_voronoi(8));
+    }
+}
@@ -0,0 +1,84 @@
+// Minimal SVG writing, for taking geometry into vector tools and plotters.
+//
+// Takes positions in the same coordinates as a nannou window (y up) and flips
+// them into SVG's y-down space, with the view box covering `view`.
+
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use nannou::geom::Rect;
+
+use crate::pos::Pos;
+
+pub struct Svg {
+    view: Rect,
+    body: String,
+}
+
+impl Svg {
+    pub fn new(view: Rect) -> Self {
+        Svg {
+            view,
+            body: String::new(),
+        }
+    }
+
+    // a closed outline; `style` is a CSS declaration list, e.g.
+    // "fill:none;stroke:black"
+    pub fn polygon(&mut self, points: &[Pos], style: &str) {
+        let _ = writeln!(
+            self.body,
+            r#"  <polygon points="{}" style="{}"/>"#,
+            points_attr(points),
+            style
+        );
+    }
+
+    pub fn polyline(&mut self, points: &[Pos], style: &str) {
+        let _ = writeln!(
+            self.body,
+            r#"  <polyline points="{}" style="{}"/>"#,
+            points_attr(points),
+            style
+        );
+    }
+
+    pub fn circle(&mut self, centre: Pos, radius: f32, style: &str) {
+        let _ = writeln!(
+            self.body,
+            r#"  <circle cx="{:.2}" cy="{:.2}" r="{:.2}" style="{}"/>"#,
+            centre.x, -centre.y, radius, style
+        );
+    }
+
+    pub fn to_svg_string(&self) -> String {
+        let (w, h) = (self.view.w(), self.view.h());
+        format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                "\n",
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.2}" height="{h:.2}" viewBox="{x:.2} {y:.2} {w:.2} {h:.2}">"#,
+                "\n{body}</svg>\n"
+            ),
+            x = self.view.left(),
+            y = -self.view.top(),
+            w = w,
+            h = h,
+            body = self.body,
+        )
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_svg_string())
+    }
+}
+
+fn points_attr(points: &[Pos]) -> String {
+    points
+        .iter()
+        .map(|p| format!("{:.2},{:.2}", p.x, -p.y))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,420 @@
+// Reading outlines back out of SVG files: every `<path>`'s `d` attribute,
+// with lines, cubic and quadratic Béziers and elliptical arcs flattened into
+// polylines.
+//
+// This is nowhere near a full SVG reader. Other shapes, transforms and units
+// are ignored, so paths should be in the file's own coordinates, as most
+// editors write them once a drawing is flattened to paths.
+
+use std::f32::consts::PI;
+
+use crate::pos::Pos;
+
+// lines each curve is flattened into
+const CURVE_SEGMENTS: usize = 32;
+// the angle of arc each line of a flattened arc spans
+const ARC_STEP: f32 = PI / 32.0;
+
+// The path data of every `<path>` in an SVG document, flattened, as one
+// polyline per subpath in SVG's y-down coordinates.
+pub fn parse_svg(text: &str) -> Result<Vec<Vec<Pos>>, String> {
+    let mut subpaths = Vec::new();
+    for tag in text.split("<path").skip(1) {
+        let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+        if let Some(d) = attribute(tag, "d") {
+            subpaths.extend(parse_path(d)?);
+        }
+    }
+    match subpaths.is_empty() {
+        true => Err("no <path> outlines found".to_string()),
+        false => Ok(subpaths),
+    }
+}
+
+// the value of attribute `name` in the inside of a tag
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    while let Some(at) = rest.find(name) {
+        let before = rest[..at].chars().next_back();
+        let after = rest[at + name.len()..].trim_start();
+        rest = &rest[at + name.len()..];
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        if let Some(value) = after.strip_prefix('=') {
+            let value = value.trim_start();
+            let quote = value.chars().next()?;
+            if quote == '"' || quote == '\'' {
+                let value = &value[1..];
+                return value.find(quote).map(|end| &value[..end]);
+            }
+        }
+    }
+    None
+}
+
+// Path data (the `d` attribute), flattened into one polyline per subpath.
+pub fn parse_path(d: &str) -> Result<Vec<Vec<Pos>>, String> {
+    let mut tokens = Tokens { text: d, at: 0 };
+    let mut subpaths: Vec<Vec<Pos>> = Vec::new();
+    let mut current = Vec::new();
+    let (mut pen, mut start) = (Pos::new(0.0, 0.0), Pos::new(0.0, 0.0));
+    // the last command, and the last curve's last control point (as 'C' or
+    // 'Q' for its kind) for smooth curves to reflect
+    let mut last = None;
+    let mut control: Option<(char, Pos)> = None;
+    while !tokens.done() {
+        let c = match tokens.command() {
+            Some(c) => c,
+            // more numbers repeat the last command, except that extra pairs
+            // after a moveto are linetos
+            None => match last {
+                Some('M') => 'L',
+                Some('m') => 'l',
+                Some(c) if !matches!(c, 'Z' | 'z') => c,
+                _ => return Err(format!("expected a command at {}", tokens.at)),
+            },
+        };
+        last = Some(c);
+        let origin = match c.is_ascii_lowercase() {
+            true => pen,
+            false => Pos::new(0.0, 0.0),
+        };
+        let point = |tokens: &mut Tokens| -> Result<Pos, String> {
+            Ok(origin + Pos::new(tokens.number()?, tokens.number()?))
+        };
+        // the control point to reflect for a smooth curve of `kind`; without
+        // a curve of that kind just before, it's the pen itself
+        let reflected = |kind: char| match control {
+            Some((k, p)) if k == kind => pen * 2.0 - p,
+            _ => pen,
+        };
+        let mut next_control = None;
+        match c.to_ascii_uppercase() {
+            'M' => {
+                pen = point(&mut tokens)?;
+                start = pen;
+                if current.len() > 1 {
+                    subpaths.push(current);
+                }
+                current = vec![pen];
+            }
+            'L' => {
+                pen = point(&mut tokens)?;
+                current.push(pen);
+            }
+            'H' => {
+                pen.x = origin.x + tokens.number()?;
+                current.push(pen);
+            }
+            'V' => {
+                pen.y = origin.y + tokens.number()?;
+                current.push(pen);
+            }
+            'C' | 'S' => {
+                let c1 = match c.to_ascii_uppercase() {
+                    'C' => point(&mut tokens)?,
+                    _ => reflected('C'),
+                };
+                let (c2, end) = (point(&mut tokens)?, point(&mut tokens)?);
+                cubic(&mut current, [pen, c1, c2, end]);
+                pen = end;
+                next_control = Some(('C', c2));
+            }
+            'Q' | 'T' => {
+                let c1 = match c.to_ascii_uppercase() {
+                    'Q' => point(&mut tokens)?,
+                    _ => reflected('Q'),
+                };
+                let end = point(&mut tokens)?;
+                // a quadratic is a cubic with both controls 2/3 of the way
+                // to the shared one
+                let to = |p: Pos| p + (c1 - p) * (2.0 / 3.0);
+                cubic(&mut current, [pen, to(pen), to(end), end]);
+                pen = end;
+                next_control = Some(('Q', c1));
+            }
+            'A' => {
+                let radii = Pos::new(tokens.number()?.abs(), tokens.number()?.abs());
+                let rotation = tokens.number()?.to_radians();
+                let (large, sweep) = (tokens.flag()?, tokens.flag()?);
+                let end = point(&mut tokens)?;
+                arc(&mut current, pen, radii, rotation, large, sweep, end);
+                pen = end;
+            }
+            'Z' => {
+                pen = start;
+                current.push(pen);
+            }
+            _ => return Err(format!("unsupported path command '{}'", c)),
+        }
+        control = next_control;
+    }
+    if current.len() > 1 {
+        subpaths.push(current);
+    }
+    Ok(subpaths)
+}
+
+// flatten a cubic Bézier from p[0] to p[3] onto the end of `out`
+fn cubic(out: &mut Vec<Pos>, p: [Pos; 4]) {
+    for i in 1..=CURVE_SEGMENTS {
+        let t = i as f32 / CURVE_SEGMENTS as f32;
+        let u = 1.0 - t;
+        out.push(
+            p[0] * (u * u * u)
+                + p[1] * (3.0 * u * u * t)
+                + p[2] * (3.0 * u * t * t)
+                + p[3] * (t * t * t),
+        );
+    }
+}
+
+// flatten an elliptical arc onto the end of `out`, converting SVG's endpoint
+// form into a centre and start and end angles as in the SVG spec's
+// implementation notes
+fn arc(
+    out: &mut Vec<Pos>,
+    from: Pos,
+    radii: Pos,
+    rotation: f32,
+    large: bool,
+    sweep: bool,
+    to: Pos,
+) {
+    if from == to {
+        return;
+    }
+    let (mut rx, mut ry) = (radii.x, radii.y);
+    if rx == 0.0 || ry == 0.0 {
+        out.push(to);
+        return;
+    }
+    let (sin, cos) = rotation.sin_cos();
+    let half = (from - to) * 0.5;
+    let x1 = cos * half.x + sin * half.y;
+    let y1 = -sin * half.x + cos * half.y;
+    // radii too small to reach are scaled up until they just do
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let sign = if large == sweep { -1.0 } else { 1.0 };
+    let coef = sign * (num / den).max(0.0).sqrt();
+    let (cx1, cy1) = (coef * rx * y1 / ry, -coef * ry * x1 / rx);
+    let mid = (from + to) * 0.5;
+    let centre = Pos::new(cos * cx1 - sin * cy1 + mid.x, sin * cx1 + cos * cy1 + mid.y);
+    let start = ((y1 - cy1) / ry).atan2((x1 - cx1) / rx);
+    let end = ((-y1 - cy1) / ry).atan2((-x1 - cx1) / rx);
+    let mut sweep_angle = end - start;
+    if sweep && sweep_angle < 0.0 {
+        sweep_angle += 2.0 * PI;
+    } else if !sweep && sweep_angle > 0.0 {
+        sweep_angle -= 2.0 * PI;
+    }
+    let segments = ((sweep_angle.abs() / ARC_STEP).ceil() as usize).max(1);
+    for i in 1..=segments {
+        let theta = start + sweep_angle * i as f32 / segments as f32;
+        let (s, c) = theta.sin_cos();
+        out.push(centre + Pos::new(rx * c * cos - ry * s * sin, rx * c * sin + ry * s * cos));
+    }
+}
+
+// the commands, numbers and arc flags of path data, in order
+struct Tokens<'a> {
+    text: &'a str,
+    at: usize,
+}
+
+impl Tokens<'_> {
+    fn skip_separators(&mut self) {
+        let rest = &self.text[self.at..];
+        let trimmed = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        self.at += rest.len() - trimmed.len();
+    }
+
+    fn done(&mut self) -> bool {
+        self.skip_separators();
+        self.at == self.text.len()
+    }
+
+    // the next command letter, if that's what's next
+    fn command(&mut self) -> Option<char> {
+        self.skip_separators();
+        let c = self.text[self.at..].chars().next()?;
+        match c.is_ascii_alphabetic() {
+            true => {
+                self.at += 1;
+                Some(c)
+            }
+            false => None,
+        }
+    }
+
+    fn number(&mut self) -> Result<f32, String> {
+        self.skip_separators();
+        let bytes = self.text.as_bytes();
+        let start = self.at;
+        let mut end = start;
+        let digits = |end: &mut usize| {
+            while *end < bytes.len() && bytes[*end].is_ascii_digit() {
+                *end += 1;
+            }
+        };
+        if end < bytes.len() && matches!(bytes[end], b'+' | b'-') {
+            end += 1;
+        }
+        digits(&mut end);
+        // a second point starts the next number, as in "0.5.5"
+        if end < bytes.len() && bytes[end] == b'.' {
+            end += 1;
+            digits(&mut end);
+        }
+        if end < bytes.len() && matches!(bytes[end], b'e' | b'E') {
+            let mut exponent = end + 1;
+            if exponent < bytes.len() && matches!(bytes[exponent], b'+' | b'-') {
+                exponent += 1;
+            }
+            if exponent < bytes.len() && bytes[exponent].is_ascii_digit() {
+                digits(&mut exponent);
+                end = exponent;
+            }
+        }
+        let number = self.text[start..end]
+            .parse()
+            .map_err(|_| format!("expected a number at {}", start))?;
+        self.at = end;
+        Ok(number)
+    }
+
+    // an arc's large-arc or sweep flag, which needn't be followed by a
+    // separator
+    fn flag(&mut self) -> Result<bool, String> {
+        self.skip_separators();
+        let flag = match self.text[self.at..].chars().next() {
+            Some('0') => false,
+            Some('1') => true,
+            _ => return Err(format!("expected an arc flag at {}", self.at)),
+        };
+        self.at += 1;
+        Ok(flag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(d: &str) -> Vec<Vec<(f32, f32)>> {
+        let subpaths = parse_path(d).unwrap();
+        subpaths
+            .into_iter()
+            .map(|line| line.into_iter().map(|p| (p.x, p.y)).collect())
+            .collect()
+    }
+
+    fn close(a: &[Vec<(f32, f32)>], b: &[Vec<(f32, f32)>]) -> bool {
+        a.len() == b.len()
+            && a.iter().zip(b).all(|(a, b)| {
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b)
+                        .all(|(a, b)| (a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4)
+            })
+    }
+
+    #[test]
+    fn pairs_after_a_moveto_are_linetos() {
+        assert_eq!(
+            points("M 0 0 10 0 10 10"),
+            [vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]]
+        );
+        assert_eq!(
+            points("m 1 1 2 0 0 2"),
+            [vec![(1.0, 1.0), (3.0, 1.0), (3.0, 3.0)]]
+        );
+    }
+
+    #[test]
+    fn relative_commands_are_from_the_pen() {
+        let square = vec![
+            (10.0, 10.0),
+            (15.0, 10.0),
+            (15.0, 15.0),
+            (10.0, 15.0),
+            (10.0, 10.0),
+        ];
+        assert_eq!(points("M 10 10 l 5 0 v 5 h -5 z"), [square]);
+        // a second relative moveto is from where the first subpath closed
+        assert_eq!(
+            points("m 1 1 l 1 0 z m 5 0 l 1 0"),
+            [
+                vec![(1.0, 1.0), (2.0, 1.0), (1.0, 1.0)],
+                vec![(6.0, 1.0), (7.0, 1.0)]
+            ]
+        );
+    }
+
+    #[test]
+    fn smooth_curves_reflect_the_last_control() {
+        assert!(close(
+            &points("M0 0 C 0 10 10 10 10 0 S 20 -10 20 0"),
+            &points("M0 0 C 0 10 10 10 10 0 C 10 -10 20 -10 20 0"),
+        ));
+        assert!(close(
+            &points("M0 0 Q 5 10 10 0 T 20 0"),
+            &points("M0 0 Q 5 10 10 0 Q 15 -10 20 0"),
+        ));
+        // with no curve of the same kind before, the control is the pen
+        assert!(close(
+            &points("M0 0 L 10 0 S 20 10 20 0"),
+            &points("M0 0 L 10 0 C 10 0 20 10 20 0"),
+        ));
+    }
+
+    #[test]
+    fn a_second_point_starts_a_new_number() {
+        assert_eq!(points("M0.5.5L1.5.5"), [vec![(0.5, 0.5), (1.5, 0.5)]]);
+        assert_eq!(points("M-1-2L1e1-2"), [vec![(-1.0, -2.0), (10.0, -2.0)]]);
+    }
+
+    #[test]
+    fn arc_flags_need_no_separators() {
+        let arc = points("M0 0A5 5 0 1010 0");
+        assert!(close(&arc, &points("M 0 0 A 5 5 0 1 0 10 0")));
+        // half a circle round (5, 0), ending where it should
+        let line = &arc[0];
+        assert!(line.len() > 2);
+        for &(x, y) in line {
+            assert!(((x - 5.0).hypot(y) - 5.0).abs() < 1e-3);
+        }
+        let end = line[line.len() - 1];
+        assert!((end.0 - 10.0).abs() < 1e-3 && end.1.abs() < 1e-3);
+    }
+
+    #[test]
+    fn bad_path_data_is_an_error() {
+        for d in [
+            "M 0 0 L 1",
+            "10 10",
+            "M 0 0 X 1 2",
+            "M 0 0 A 5 5 0 2 0 10 0",
+            "M 0 0 L a b",
+        ] {
+            assert!(parse_path(d).is_err(), "{}", d);
+        }
+        assert!(parse_svg("<svg><rect width='10'/></svg>").is_err());
+    }
+
+    #[test]
+    fn reads_every_paths_d() {
+        let svg =
+            r#"<svg><path id="a" d="M0 0 L1 0"/><g><path fill='none' d='M5 5 l 0 1'/></g></svg>"#;
+        let subpaths = parse_svg(svg).unwrap();
+        assert_eq!(subpaths.len(), 2);
+        assert_eq!(subpaths[1], [Pos::new(5.0, 5.0), Pos::new(5.0, 6.0)]);
+    }
+}
@@ -0,0 +1,201 @@
+// Targetting modes for the perlin agents: the points the cloud is pulled
+// towards each frame, and how agents are shared out between them.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::Agent;
+use crate::pos::Pos;
+use crate::rng::SimRng;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TargetMode {
+    Circle,
+    FigureEight,
+    Lissajous,
+    Rose,
+    Spiral,
+    Spline,
+    Svg,
+    Noise,
+    Average,
+    Mouse,
+}
+
+impl TargetMode {
+    // the next mode in the D-key cycle
+    pub fn next(self) -> TargetMode {
+        match self {
+            TargetMode::Circle => TargetMode::FigureEight,
+            TargetMode::FigureEight => TargetMode::Lissajous,
+            TargetMode::Lissajous => TargetMode::Rose,
+            TargetMode::Rose => TargetMode::Spiral,
+            TargetMode::Spiral => TargetMode::Spline,
+            TargetMode::Spline => TargetMode::Svg,
+            TargetMode::Svg => TargetMode::Noise,
+            TargetMode::Noise => TargetMode::Average,
+            TargetMode::Average => TargetMode::Mouse,
+            TargetMode::Mouse => TargetMode::Circle,
+        }
+    }
+}
+
+// How agents are shared out between several targets.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Assignment {
+    // each agent chases whichever target is closest, switching as they move
+    Nearest,
+    // agents are dealt out to the targets in turn
+    RoundRobin,
+    // agents chase the target matching their band of `z_offset`, which is
+    // also what colours them
+    Band,
+    // each agent picks a target at random, and sticks with it
+    Random,
+}
+
+impl Assignment {
+    // the next policy in the A-key cycle
+    pub fn next(self) -> Assignment {
+        match self {
+            Assignment::Nearest => Assignment::RoundRobin,
+            Assignment::RoundRobin => Assignment::Band,
+            Assignment::Band => Assignment::Random,
+            Assignment::Random => Assignment::Nearest,
+        }
+    }
+}
+
+// One of the points the cloud is pulled towards.
+#[derive(Copy, Clone, Debug)]
+pub struct Target {
+    pub pos: Pos,
+    pub mode: TargetMode,
+}
+
+impl Target {
+    pub fn new(mode: TargetMode) -> Target {
+        Target {
+            pos: Pos::new(0.0, 0.0),
+            mode,
+        }
+    }
+}
+
+// the index of the target closest to `pos`
+pub fn nearest(targets: &[Target], pos: Pos) -> usize {
+    targets
+        .iter()
+        .map(|t| (t.pos - pos).magnitude())
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or(0, |(i, _)| i)
+}
+
+// point each agent at one of `targets`, as `assignment` shares them out
+pub fn assign(assignment: Assignment, targets: &[Target], agents: &mut [Agent], rng: &mut SimRng) {
+    let n = targets.len();
+    match assignment {
+        Assignment::Nearest => {
+            agents
+                .iter_mut()
+                .for_each(|a| a.target = nearest(targets, a.pos));
+        }
+        Assignment::RoundRobin => {
+            agents
+                .iter_mut()
+                .enumerate()
+                .for_each(|(i, a)| a.target = i % n);
+        }
+        Assignment::Band => {
+            agents
+                .iter_mut()
+                .for_each(|a| a.target = ((a.z_offset * n as f32) as usize).min(n - 1));
+        }
+        Assignment::Random => {
+            for a in agents.iter_mut() {
+                a.target = rng.gen_range(0..n);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nannou::geom::Rect;
+
+    use super::*;
+    use crate::motion;
+    use crate::rng;
+
+    // three targets in a row, left to right
+    fn targets() -> Vec<Target> {
+        [-100.0, 0.0, 100.0]
+            .into_iter()
+            .map(|x| Target {
+                pos: Pos::new(x, 0.0),
+                ..Target::new(TargetMode::Circle)
+            })
+            .collect()
+    }
+
+    fn agents(count: usize, seed: u64) -> Vec<Agent> {
+        let (win, mut rng) = (Rect::from_w_h(400.0, 400.0), rng::from_seed(seed));
+        let motion = motion::Settings::default();
+        (0..count)
+            .map(|_| Agent::new(win, false, 1.0, motion, &mut rng))
+            .collect()
+    }
+
+    fn assigned(assignment: Assignment, agents: &mut [Agent], seed: u64) -> Vec<usize> {
+        assign(assignment, &targets(), agents, &mut rng::from_seed(seed));
+        agents.iter().map(|a| a.target).collect()
+    }
+
+    #[test]
+    fn nearest_goes_to_the_closest() {
+        let mut agents = agents(5, 1);
+        for (a, x) in agents.iter_mut().zip([-300.0, -60.0, -40.0, 45.0, 70.0]) {
+            a.pos = Pos::new(x, 80.0);
+        }
+        assert_eq!(
+            assigned(Assignment::Nearest, &mut agents, 1),
+            [0, 0, 1, 1, 2]
+        );
+    }
+
+    #[test]
+    fn round_robin_deals_them_out_in_turn() {
+        let mut agents = agents(7, 1);
+        assert_eq!(
+            assigned(Assignment::RoundRobin, &mut agents, 1),
+            [0, 1, 2, 0, 1, 2, 0]
+        );
+    }
+
+    #[test]
+    fn bands_split_z_offsets_evenly() {
+        let mut agents = agents(6, 1);
+        for (a, z) in agents.iter_mut().zip([0.0, 0.33, 0.34, 0.66, 0.67, 0.999]) {
+            a.z_offset = z;
+        }
+        assert_eq!(
+            assigned(Assignment::Band, &mut agents, 1),
+            [0, 0, 1, 1, 2, 2]
+        );
+    }
+
+    #[test]
+    fn random_picks_the_same_for_the_same_seed() {
+        let picks = assigned(Assignment::Random, &mut agents(300, 1), 4);
+        assert_eq!(picks, assigned(Assignment::Random, &mut agents(300, 1), 4));
+        assert_ne!(picks, assigned(Assignment::Random, &mut agents(300, 1), 5));
+        // roughly evenly, whatever the agents' positions
+        for target in 0..3 {
+            let count = picks.iter().filter(|&&t| t == target).count();
+            assert!((70..130).contains(&count), "{} for {}", count, target);
+        }
+    }
+}
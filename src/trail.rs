@@ -0,0 +1,195 @@
+// Trails: where every agent has been over the last so many frames, and what
+// colour it was there, kept as a ring of frames of bare positions (x and y in
+// separate arrays) and packed colours rather than whole agents, so thousands
+// of agents can leave long trails cheaply.
+
+use crate::color::{self, Color};
+use crate::pos::Pos;
+
+pub struct Trails {
+    // how many frames are kept
+    length: usize,
+    // how many agents each frame holds
+    width: usize,
+    // `length` frames of `width` positions and colours each, frame by frame
+    xs: Vec<f32>,
+    ys: Vec<f32>,
+    // as 0xRRGGBB
+    rgbs: Vec<u32>,
+    // the slot the next frame goes in, and how many slots are filled
+    next: usize,
+    filled: usize,
+}
+
+impl Trails {
+    pub fn new(length: usize) -> Trails {
+        Trails {
+            length: length.max(1),
+            width: 0,
+            xs: Vec::new(),
+            ys: Vec::new(),
+            rgbs: Vec::new(),
+            next: 0,
+            filled: 0,
+        }
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    // how many frames have been recorded, up to `length`
+    pub fn frames(&self) -> usize {
+        self.filled
+    }
+
+    // forget everything recorded so far
+    pub fn clear(&mut self) {
+        self.next = 0;
+        self.filled = 0;
+    }
+
+    // change how many frames are kept, keeping the newest of those recorded
+    pub fn set_length(&mut self, length: usize) {
+        let length = length.max(1);
+        if length == self.length {
+            return;
+        }
+        let kept = self.filled.min(length);
+        let mut xs = Vec::with_capacity(length * self.width);
+        let mut ys = Vec::with_capacity(length * self.width);
+        let mut rgbs = Vec::with_capacity(length * self.width);
+        for age in (0..kept).rev() {
+            let frame = self.frame(age);
+            xs.extend_from_slice(frame.xs);
+            ys.extend_from_slice(frame.ys);
+            rgbs.extend_from_slice(frame.rgbs);
+        }
+        xs.resize(length * self.width, 0.0);
+        ys.resize(length * self.width, 0.0);
+        rgbs.resize(length * self.width, 0);
+        (self.xs, self.ys, self.rgbs) = (xs, ys, rgbs);
+        self.length = length;
+        self.filled = kept;
+        self.next = kept % length;
+    }
+
+    // record where everyone is now, and their colours, as the newest frame,
+    // dropping the oldest if the trails are full. A different number of
+    // agents than last time starts the trails afresh.
+    pub fn push(&mut self, samples: impl ExactSizeIterator<Item = (Pos, Color)>) {
+        if samples.len() != self.width {
+            self.width = samples.len();
+            self.xs = vec![0.0; self.length * self.width];
+            self.ys = vec![0.0; self.length * self.width];
+            self.rgbs = vec![0; self.length * self.width];
+            self.clear();
+        }
+        let start = self.next * self.width;
+        for (i, (pos, color)) in samples.enumerate() {
+            self.xs[start + i] = pos.x;
+            self.ys[start + i] = pos.y;
+            self.rgbs[start + i] = pack(color);
+        }
+        self.next = (self.next + 1) % self.length;
+        self.filled = (self.filled + 1).min(self.length);
+    }
+
+    // every agent's position and colour `age` frames ago (0 being the newest
+    // frame)
+    pub fn frame(&self, age: usize) -> Frame<'_> {
+        assert!(age < self.filled, "only {} frames recorded", self.filled);
+        let slot = (self.next + self.length - 1 - age) % self.length;
+        let range = slot * self.width..(slot + 1) * self.width;
+        Frame {
+            xs: &self.xs[range.clone()],
+            ys: &self.ys[range.clone()],
+            rgbs: &self.rgbs[range],
+        }
+    }
+}
+
+// One frame of the trails, agent by agent.
+#[derive(Copy, Clone)]
+pub struct Frame<'a> {
+    pub xs: &'a [f32],
+    pub ys: &'a [f32],
+    pub rgbs: &'a [u32],
+}
+
+impl Frame<'_> {
+    pub fn pos(&self, i: usize) -> Pos {
+        Pos::new(self.xs[i], self.ys[i])
+    }
+
+    // opaque, as colours are stored without their alpha
+    pub fn color(&self, i: usize) -> Color {
+        let rgb = self.rgbs[i];
+        color::rgb8((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+    }
+}
+
+fn pack(color: Color) -> u32 {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u32;
+    channel(color.red) << 16 | channel(color.green) << 8 | channel(color.blue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one agent, at x = `i` and coloured by it too
+    fn sample(i: usize) -> (Pos, Color) {
+        (
+            Pos::new(i as f32, 0.0),
+            color::rgb8(i as u8, 255 - i as u8, 7),
+        )
+    }
+
+    fn xs(trails: &Trails) -> Vec<f32> {
+        (0..trails.frames())
+            .map(|age| trails.frame(age).xs[0])
+            .collect()
+    }
+
+    #[test]
+    fn keeps_the_newest_frames_round_the_ring() {
+        let mut trails = Trails::new(3);
+        for i in 0..5 {
+            trails.push([sample(i)].into_iter());
+        }
+        assert_eq!(trails.frames(), 3);
+        assert_eq!(xs(&trails), [4.0, 3.0, 2.0]);
+        // colours go round with their positions, and come back out as they
+        // went in
+        for age in 0..3 {
+            assert_eq!(trails.frame(age).color(0), sample(4 - age).1);
+        }
+    }
+
+    #[test]
+    fn resizing_keeps_the_newest() {
+        let mut trails = Trails::new(4);
+        for i in 0..6 {
+            trails.push([sample(i)].into_iter());
+        }
+        trails.set_length(2);
+        assert_eq!(xs(&trails), [5.0, 4.0]);
+        trails.set_length(5);
+        assert_eq!(xs(&trails), [5.0, 4.0]);
+        for i in 6..10 {
+            trails.push([sample(i)].into_iter());
+        }
+        assert_eq!(xs(&trails), [9.0, 8.0, 7.0, 6.0, 5.0]);
+        assert_eq!(trails.frame(4).color(0), sample(5).1);
+    }
+
+    #[test]
+    fn more_agents_start_afresh() {
+        let mut trails = Trails::new(3);
+        trails.push([sample(0)].into_iter());
+        trails.push([sample(1), sample(2)].into_iter());
+        assert_eq!(trails.frames(), 1);
+        assert_eq!(trails.frame(0).pos(1), Pos::new(2.0, 0.0));
+    }
+}
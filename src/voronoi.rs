@@ -1,141 +1,190 @@
-// Draw a collection of voronoi cells, animated while their sites randomly
-// wander around.
+// A collection of voronoi cells, animated while their sites randomly wander
+// around.
 
 // There's some mildly annoying mixing of float sizes back and forth here,
 // because the voronoice lib uses f64s while nannou and rust's math constants
 // use f32s.
 
-use nannou::prelude::*;
+use nannou::geom::{Rect, Vec2};
+use nannou::noise::{NoiseFn, Perlin, Seedable};
+use palette::Srgb;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use voronoice::*;
 
-pub mod pos;
-use pos::Pos;
+use crate::cellfill::{self, Fill, GRAPH_COLORS};
+use crate::color::{Color, BLACK, WHITE};
+use crate::colormap::Hex;
+use crate::grid::Grid;
+use crate::painter::Painter;
+use crate::par;
+use crate::pos::Pos;
+use crate::rng::{self, SimRng};
+use crate::svg::Svg;
+use crate::wanderer::Wanderer;
 
-fn main() {
-    nannou::app(model).update(update).run();
-}
+// the fewest sites that make a valid diagram
+pub const MIN_SITES: usize = 3;
 
-#[derive(Clone)]
-struct Agent {
-    pos: Pos,
-    angle: f32,
-    step_size: f32,
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdateMode {
+    One,
+    Two,
 }
 
-impl Agent {
-    fn update1(&mut self, win: Rect) {
-        // velocity random walk
-        // add a little noise to the angle
-        self.angle += 0.2 * random_range(-1.0, 1.0);
-        // step the position
-        self.pos.x += self.angle.cos() * self.step_size;
-        self.pos.y += self.angle.sin() * self.step_size;
-        // turn around if approaching the edges
-        if self.pos.x + self.angle.cos() * self.step_size * 20.0 < win.left().into() {
-            self.angle = PI - self.angle;
-        }
-        if self.pos.x + self.angle.cos() * self.step_size * 20.0 > win.right().into() {
-            self.angle = PI - self.angle;
-        }
-        if self.pos.y + self.angle.sin() * self.step_size * 20.0 < win.bottom().into() {
-            self.angle = -self.angle;
-        }
-        if self.pos.y + self.angle.sin() * self.step_size * 20.0 > win.top().into() {
-            self.angle = -self.angle;
+impl UpdateMode {
+    pub fn next(self) -> UpdateMode {
+        match self {
+            UpdateMode::One => UpdateMode::Two,
+            UpdateMode::Two => UpdateMode::One,
         }
     }
+}
 
-    fn update2(&mut self, win: Rect, sites_vec: &Vec<Pos>) {
-        let mut next_pos = self.pos;
-
-        // repel from every site with inverse square power
-        for site in sites_vec.iter() {
-            let dxy = *site - self.pos;
-            // sites vec includes current agent; skip if match
-            if dxy.x.abs() < std::f32::EPSILON && dxy.y.abs() < std::f32::EPSILON {
-                continue;
-            }
-            let force = dxy.magnitude().pow(-3.0);
-            let scalar = 1000.0;
-            next_pos = next_pos - dxy * force * scalar;
-        }
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    // at least `MIN_SITES`
+    pub agent_count: usize,
+    pub step_size: f32, // in pixels per frame, for the random walk
+    // most the random walk can turn in one step, in radians
+    pub wander_jitter: f32,
+    // strength of the inverse-square push between sites, and from the edges
+    pub site_repulsion: f32,
+    pub bound_repulsion: f32,
+    // sites further apart than this don't push each other at all, which keeps
+    // large diagrams fast; 0 for no cutoff
+    pub repulsion_cutoff: f32,
+    pub update_mode: UpdateMode,
+    // how cells are coloured in, outlined, inset and rounded
+    pub fill: cellfill::Settings,
+}
 
-        // then repel from bounds
-        let bounds: Vec<Pos> = vec![
-            Pos::new(win.left(), self.pos.y),   // left
-            Pos::new(win.right(), self.pos.y),  // right
-            Pos::new(self.pos.x, win.top()),    // top
-            Pos::new(self.pos.x, win.bottom()), // bottom
-        ];
-        for bound in bounds.iter() {
-            let dxy = *bound - self.pos;
-            let force = dxy.magnitude().pow(-2.0);
-            let scalar = 50.0;
-            next_pos = next_pos - dxy * force * scalar;
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            agent_count: 100,
+            step_size: 0.3,
+            wander_jitter: 0.2,
+            site_repulsion: 1000.0,
+            bound_repulsion: 50.0,
+            repulsion_cutoff: 0.0,
+            update_mode: UpdateMode::Two,
+            fill: cellfill::Settings::default(),
         }
-
-        self.pos = next_pos;
     }
 }
 
-enum UpdateMode {
-    One,
-    Two,
+impl Settings {
+    // the repulsion cutoff, with "none" as infinity
+    fn cutoff(&self) -> f32 {
+        match self.repulsion_cutoff > 0.0 {
+            true => self.repulsion_cutoff,
+            false => f32::INFINITY,
+        }
+    }
 }
 
-struct Model {
-    agent_count: i32,
-    agents: Vec<Agent>,
-    voronoi: Voronoi,
-    win: Rect,
-    update_mode: UpdateMode,
+pub struct Sim {
+    pub agents: Vec<Wanderer>,
+    pub voronoi: Voronoi,
+    pub win: Rect,
+    pub update_mode: UpdateMode,
+    pub seed: u64,
+    pub settings: Settings,
+    // where the sites were before the last update, and the cell outlines
+    // then, to interpolate from
+    prev_sites: Vec<Pos>,
+    prev_cells: Vec<Vec<Pos>>,
+    // the sites bucketed by position, rebuilt each update
+    grid: Grid,
+    // for the noise fill
+    noise: Perlin,
+    // each cell's colour for the graph fill, kept from update to update so
+    // cells hold onto theirs
+    graph_colors: Vec<usize>,
+    rng: SimRng,
 }
 
-impl Model {
-    fn new(win: Rect) -> Self {
-        let agent_count = 100;
-        let agents: Vec<Agent> = Model::build_agents(agent_count, win);
-        let voronoi = Model::build_voronoi(
-            agents
-                .clone()
-                .into_iter()
-                .map(|a| Point {
-                    x: a.pos.x as f64,
-                    y: a.pos.y as f64,
-                })
-                .collect(),
-            win,
-        );
-        let update_mode = UpdateMode::Two;
+impl Sim {
+    pub fn new(win: Rect, seed: u64, settings: Settings) -> Self {
+        let mut rng = rng::from_seed(seed);
+        let agents = Sim::build_agents(&settings, win, &mut rng);
+        let voronoi = Sim::build_voronoi(&agents, win);
+        let update_mode = settings.update_mode;
+        let prev_sites = agents.iter().map(|a| a.pos).collect();
+        let prev_cells = cells_of(&voronoi);
+        let grid = Sim::build_grid(&settings, win);
 
-        Model {
-            agent_count,
+        let mut sim = Sim {
             agents,
             voronoi,
             win,
             update_mode,
-        }
+            seed,
+            settings,
+            prev_sites,
+            prev_cells,
+            grid,
+            noise: Perlin::new().set_seed(seed as u32),
+            graph_colors: Vec::new(),
+            rng,
+        };
+        sim.color_graph();
+        sim
     }
 
-    fn build_agents(agent_count: i32, win: Rect) -> Vec<Agent> {
+    pub fn build_agents(settings: &Settings, win: Rect, rng: &mut SimRng) -> Vec<Wanderer> {
         let pad = 50.0;
-        (0..agent_count)
-            .map(|_| Agent {
-                pos: Pos::new(
-                    random_range(win.left() + pad, win.right() - pad),
-                    random_range(win.bottom() + pad, win.top() - pad),
-                ),
-                angle: random_range(-PI, PI),
-                step_size: 0.3,
+        (0..settings.agent_count)
+            .map(|_| {
+                let pos = Pos::new(
+                    rng.gen_range(win.left() + pad..win.right() - pad),
+                    rng.gen_range(win.bottom() + pad..win.top() - pad),
+                );
+                Wanderer::new(pos, settings.step_size, rng)
             })
             .collect()
     }
 
-    fn get_sites(&self) -> Vec<Pos> {
+    pub fn reset_agents(&mut self) {
+        self.agents = Sim::build_agents(&self.settings, self.win, &mut self.rng);
+        self.prev_sites = self.get_sites();
+    }
+
+    pub fn get_sites(&self) -> Vec<Pos> {
         self.agents.iter().map(|a| a.pos).collect()
     }
 
-    fn build_voronoi(sites: Vec<Point>, win: Rect) -> Voronoi {
+    // an empty grid with cells the size of the repulsion cutoff, so only the
+    // cells around a site need searching
+    fn build_grid(settings: &Settings, win: Rect) -> Grid {
+        Grid::new(win, settings.cutoff())
+    }
+
+    // the sites partway through the last update, `alpha` from 0 (where they
+    // were before it) to 1 (where they are now); just where they are now if
+    // sites have been added or removed since
+    pub fn sites_at(&self, alpha: f32) -> Vec<Pos> {
+        if alpha >= 1.0 || self.prev_sites.len() != self.agents.len() {
+            return self.get_sites();
+        }
+        self.prev_sites
+            .iter()
+            .zip(self.agents.iter())
+            .map(|(prev, a)| prev.lerp(a.pos, alpha))
+            .collect()
+    }
+
+    pub fn build_voronoi(agents: &[Wanderer], win: Rect) -> Voronoi {
+        let sites = agents
+            .iter()
+            .map(|a| Point {
+                x: a.pos.x as f64,
+                y: a.pos.y as f64,
+            })
+            .collect();
         VoronoiBuilder::default()
             .set_sites(sites)
             .set_bounding_box(BoundingBox::new_centered(win.w().into(), win.h().into()))
@@ -143,117 +192,325 @@ impl Model {
             .expect("Provided sites don't generate a valid voronoi graph")
     }
 
-    fn rebuild_voronoi(&mut self) {
-        self.voronoi = Model::build_voronoi(
-            self.agents
-                .clone()
-                .into_iter()
-                .map(|a| Point {
-                    x: a.pos.x as f64,
-                    y: a.pos.y as f64,
+    pub fn rebuild_voronoi(&mut self) {
+        self.voronoi = Sim::build_voronoi(&self.agents, self.win);
+    }
+
+    pub fn update(&mut self) {
+        self.prev_sites = self.get_sites();
+        self.prev_cells = self.cells();
+        // update agents
+        let (win, update_mode, s) = (self.win, self.update_mode, &self.settings);
+        if update_mode == UpdateMode::Two {
+            self.grid.rebuild(self.agents.iter().map(|a| a.pos));
+        }
+        let (grid, cutoff) = (&self.grid, s.cutoff());
+        par::for_each_mut(&mut self.agents, |agent| match update_mode {
+            UpdateMode::One => agent.update1(win, s.wander_jitter),
+            UpdateMode::Two => {
+                agent.update2(win, grid, cutoff, s.site_repulsion, s.bound_repulsion)
+            }
+        });
+        // redraw voronoi cells
+        self.rebuild_voronoi();
+        self.color_graph();
+    }
+
+    // recolour the cells for the graph fill, starting from their colours
+    // before so they hold onto them; drawing between updates reuses these
+    fn color_graph(&mut self) {
+        if self.settings.fill.mode == Fill::Graph {
+            let neighbors = neighbors_of(&self.voronoi);
+            self.graph_colors = cellfill::graph_colors(&neighbors, &self.graph_colors);
+        }
+    }
+
+    // switch to new settings on the fly, keeping as much of the current state
+    // as possible
+    pub fn apply_settings(&mut self, settings: Settings) {
+        self.update_mode = settings.update_mode;
+        self.agents.truncate(settings.agent_count);
+        let extra = settings.agent_count.saturating_sub(self.agents.len());
+        let more = Settings {
+            agent_count: extra,
+            ..settings.clone()
+        };
+        self.agents
+            .extend(Sim::build_agents(&more, self.win, &mut self.rng));
+        self.agents
+            .iter_mut()
+            .for_each(|a| a.step_size = settings.step_size);
+        self.grid = Sim::build_grid(&settings, self.win);
+        self.settings = settings;
+        self.color_graph();
+    }
+
+    // create a bubble at the given position
+    pub fn add_site(&mut self, pos: Pos) {
+        // add a bit of jitter to the position, to avoid glitchy effects when
+        // creating sites on the same spot
+        let jitter = Pos::new(self.rng.gen_range(-5.0..5.0), self.rng.gen_range(-5.0..5.0));
+        self.agents.push(Wanderer::new(
+            pos + jitter,
+            self.settings.step_size,
+            &mut self.rng,
+        ));
+    }
+
+    // delete the bubble containing the given position, unless that would
+    // leave too few for a diagram
+    pub fn remove_site(&mut self, pos: Pos) {
+        if self.agents.len() <= MIN_SITES {
+            return;
+        }
+        // by definition, you can identify the voronoi cell clicked in by
+        // finding the nearest site
+        let mut nearest_site_index: Option<usize> = None;
+        let mut nearest_len = 10000.0;
+        for (index, agent) in self.agents.iter().enumerate() {
+            let len = (agent.pos - pos).magnitude();
+            if len < nearest_len {
+                nearest_site_index = Some(index);
+                nearest_len = len;
+            }
+        }
+        if let Some(index) = nearest_site_index {
+            self.agents.remove(index);
+        }
+    }
+
+    // cell outlines, in the same coordinates as the window
+    pub fn cells(&self) -> Vec<Vec<Pos>> {
+        cells_of(&self.voronoi)
+    }
+
+    // the voronoi bounding box, as a window rect
+    pub fn bounds(&self) -> Rect {
+        let bbox = self.voronoi.bounding_box();
+        Rect::from_x_y_w_h(
+            bbox.center().x as f32,
+            bbox.center().y as f32,
+            bbox.width() as f32,
+            bbox.height() as f32,
+        )
+    }
+
+    // the cell outlines partway through the last update, as for `sites_at`.
+    // Rather than building a diagram for every frame, each cell's vertices
+    // are moved from where they were before it; a cell that gained or lost
+    // vertices just shows as it is now.
+    pub fn cells_at(&self, alpha: f32) -> Vec<Vec<Pos>> {
+        let cells = self.cells();
+        if alpha >= 1.0 || self.prev_cells.len() != cells.len() {
+            return cells;
+        }
+        self.prev_cells
+            .iter()
+            .zip(cells)
+            .map(|(prev, cell)| lerp_cell(prev, cell, alpha))
+            .collect()
+    }
+
+    // draw the diagram `alpha` of the way through the last update (see
+    // `sites_at` and `cells_at`)
+    pub fn paint<P: Painter>(&self, painter: &mut P, draw_sites: bool, alpha: f32) {
+        let sites = self.sites_at(alpha);
+        let fill = &self.settings.fill;
+        painter.background(BLACK);
+        for (shape, color) in self.shapes(self.cells_at(alpha), &sites) {
+            if let Some(color) = color {
+                painter.polygon(&shape, color);
+            }
+            if fill.stroke_width > 0.0 {
+                painter.polyline_closed(&shape, fill.stroke_width, fill.stroke_color.color());
+            }
+        }
+        if draw_sites {
+            sites
+                .iter()
+                .for_each(|site| painter.point(*site, 1.0, WHITE));
+        }
+    }
+
+    // the current diagram as an SVG document, one polygon per cell and
+    // optionally a marker on each site, framed by the bounding box. Unfilled
+    // cells are outlined in black, as for a plotter.
+    pub fn to_svg(&self, draw_sites: bool) -> Svg {
+        let bounds = self.bounds();
+        let mut svg = Svg::new(bounds);
+        let fill = &self.settings.fill;
+        let stroke_width = fill.stroke_width;
+        for (shape, color) in self.shapes(self.cells(), &self.get_sites()) {
+            let style = match (color, stroke_width > 0.0) {
+                (Some(color), true) => format!(
+                    "fill:{};stroke:{};stroke-width:{}",
+                    hex(color),
+                    String::from(fill.stroke_color),
+                    stroke_width
+                ),
+                (Some(color), false) => format!("fill:{};stroke:none", hex(color)),
+                (None, _) => format!("fill:none;stroke:black;stroke-width:{}", stroke_width),
+            };
+            svg.polygon(&shape, &style);
+        }
+        if draw_sites {
+            self.get_sites()
+                .iter()
+                .filter(|site| bounds.contains(Vec2::new(site.x, site.y)))
+                .for_each(|site| svg.circle(*site, 1.5, "fill:black;stroke:none"));
+        }
+        svg
+    }
+
+    // every one of `cells`, the outlines around `sites`, inset and rounded,
+    // with its fill colour if it has one; cells the inset swallows are left
+    // out
+    fn shapes(&self, cells: Vec<Vec<Pos>>, sites: &[Pos]) -> Vec<(Vec<Pos>, Option<Color>)> {
+        let fill = &self.settings.fill;
+        let colors = self.fill_colors(sites, &cells);
+        cells
+            .iter()
+            .zip(colors)
+            .filter_map(|(cell, color)| fill.shape(cell).map(|shape| (shape, color)))
+            .collect()
+    }
+
+    // each cell's fill colour, with `cells` the outlines around `sites`, in
+    // the same order as the diagram's; nothing for cells left unfilled
+    fn fill_colors(&self, sites: &[Pos], cells: &[Vec<Pos>]) -> Vec<Option<Color>> {
+        let fill = &self.settings.fill;
+        let along = |t: f32| Some(fill.colors.color(fill.palette, t.clamp(0.0, 1.0)));
+        match fill.mode {
+            Fill::None => vec![None; cells.len()],
+            Fill::Area => {
+                let areas: Vec<f32> = cells.iter().map(|cell| cellfill::area(cell)).collect();
+                let mean = areas.iter().sum::<f32>() / areas.len().max(1) as f32;
+                let full = (mean * fill.area_range).max(1e-6);
+                areas.iter().map(|area| along(area / full)).collect()
+            }
+            Fill::Neighbors => neighbors_of(&self.voronoi)
+                .iter()
+                .map(|neighbors| along((neighbors.len() as f32 - 3.0) / 6.0))
+                .collect(),
+            // still, if sites have been added or removed since the last update
+            Fill::Velocity => {
+                let moved = self.prev_sites.len() == self.agents.len();
+                (0..sites.len())
+                    .map(|i| match moved {
+                        true => along(
+                            (self.agents[i].pos - self.prev_sites[i]).magnitude()
+                                / fill.speed_range,
+                        ),
+                        false => along(0.0),
+                    })
+                    .collect()
+            }
+            Fill::Noise => sites
+                .iter()
+                .map(|site| {
+                    let at = [site.x as f64, site.y as f64].map(|c| c / fill.noise_scale);
+                    along(0.5 + 0.5 * self.noise.get([at[0], at[1], 0.0]) as f32)
                 })
                 .collect(),
-            self.win,
-        );
+            Fill::Picture => sites
+                .iter()
+                .map(|site| {
+                    let picture = fill.picture()?;
+                    picture.sample(*site, self.win).map(|sample| sample.color)
+                })
+                .collect(),
+            // as coloured at the last update, cells keeping their order
+            // between diagrams
+            Fill::Graph => {
+                let last = (GRAPH_COLORS - 1) as f32;
+                (0..cells.len())
+                    .map(|i| self.graph_colors.get(i).copied().unwrap_or(0))
+                    .map(|c| along(c.min(GRAPH_COLORS - 1) as f32 / last))
+                    .collect()
+            }
+        }
     }
 }
 
-fn model(app: &App) -> Model {
-    app.new_window()
-        .size(1000, 1000)
-        .view(view)
-        .key_released(key_released)
-        .mouse_released(mouse_released)
-        .build()
-        .unwrap();
-    let win = app.window_rect();
-    Model::new(win)
+// the indices of the sites each site's cell borders
+fn neighbors_of(voronoi: &Voronoi) -> Vec<Vec<usize>> {
+    voronoi
+        .iter_cells()
+        .map(|cell| cell.iter_neighbors().collect())
+        .collect()
 }
 
-fn update(_app: &App, model: &mut Model, _update: Update) {
-    // update agents
-    let sites = model.get_sites();
-    model
-        .agents
-        .iter_mut()
-        .for_each(|agent| match model.update_mode {
-            UpdateMode::One => agent.update1(model.win),
-            UpdateMode::Two => agent.update2(model.win, &sites),
-        });
-    // redraw voronoi cells
-    model.rebuild_voronoi();
+// a cell outline `t` of the way from `prev` to `cell`, matching each vertex
+// with the one it's moved from. The diagram may list a cell's vertices
+// starting from a different one each build, so the first is matched with
+// its nearest and the rest follow in turn.
+fn lerp_cell(prev: &[Pos], cell: Vec<Pos>, t: f32) -> Vec<Pos> {
+    if cell.is_empty() || prev.len() != cell.len() {
+        return cell;
+    }
+    let first = cell[0];
+    let dist = |i: &usize| (prev[*i] - first).magnitude();
+    let start = (0..prev.len())
+        .min_by(|a, b| dist(a).total_cmp(&dist(b)))
+        .unwrap_or(0);
+    cell.iter()
+        .enumerate()
+        .map(|(i, &vert)| prev[(start + i) % prev.len()].lerp(vert, t))
+        .collect()
 }
 
-fn view(app: &App, model: &Model, frame: Frame) {
-    let draw = app.draw();
-    draw.background().color(BLACK);
-    // // draw points
-    // model.get_sites().iter().for_each(|site| {
-    //     draw.ellipse()
-    //         .x_y(site.x as f32, site.y as f32)
-    //         .radius(1.0)
-    //         .color(WHITE);
-    // });
-    // draw cell bounds
-    model.voronoi.iter_cells().for_each(|cell| {
-        // cell verts are in Points which can't Into a Vec2, stupidly
-        // so copy the cell and manually convert it ..?
-        let cell2: Vec<Vec2> = cell
-            .clone()
-            .iter_vertices()
-            .map(|vert| Vec2::new(vert.x as f32, vert.y as f32))
-            .collect();
-        draw.polyline()
-            .weight(1.0)
-            .points_closed(cell2)
-            .color(WHITE);
-    });
-    draw.to_frame(app, &frame).unwrap();
+// a colour as SVG writes it
+fn hex(color: Color) -> String {
+    let srgb: Srgb<u8> = color.color.into_format();
+    String::from(Hex(srgb))
 }
 
-fn key_released(_app: &App, model: &mut Model, key: Key) {
-    match key {
-        Key::T => {
-            model.update_mode = match model.update_mode {
-                UpdateMode::One => UpdateMode::Two,
-                UpdateMode::Two => UpdateMode::One,
-            }
-        }
-        Key::R => model.agents = Model::build_agents(model.agent_count, model.win),
-        _other_key => {}
-    }
+// the outlines of a diagram's cells, in the same coordinates as the window
+fn cells_of(voronoi: &Voronoi) -> Vec<Vec<Pos>> {
+    voronoi
+        .iter_cells()
+        .map(|cell| {
+            cell.iter_vertices()
+                .map(|vert| Pos::new(vert.x as f32, vert.y as f32))
+                .collect()
+        })
+        .collect()
 }
 
-fn mouse_released(app: &App, model: &mut Model, mouse: MouseButton) {
-    let mouse_pos = Pos::new(app.mouse.x, app.mouse.y);
-    match mouse {
-        MouseButton::Left => {
-            // create bubble
-            // add a bit of jitter to the mouse pos when creating a bubble, to avoid glitchy effects when creating sites on the same spot
-            let mouse_pos = mouse_pos + Pos::new(random_range(-5.0, 5.0), random_range(-5.0, 5.0));
-            model.agents.push(Agent {
-                pos: mouse_pos,
-                angle: random_range(-PI, PI),
-                step_size: 0.3,
-            })
-        }
-        MouseButton::Right => {
-            // delete bubble
-            // by definition, you can identify the voronoi cell clicked in by
-            // finding the nearest site
-            let mut nearest_site_index: Option<usize> = None;
-            let mut nearest_len = 10000.0;
-            for (index, agent) in model.agents.iter().enumerate() {
-                let len = (agent.pos - mouse_pos).magnitude();
-                if len < nearest_len {
-                    nearest_site_index = Some(index);
-                    nearest_len = len;
-                }
-            }
-            if let Some(index) = nearest_site_index {
-                model.agents.remove(index);
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(at: Pos, offset: usize) -> Vec<Pos> {
+        let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+        (0..4)
+            .map(|i| corners[(i + offset) % 4])
+            .map(|(x, y)| at + Pos::new(x, y))
+            .collect()
+    }
+
+    #[test]
+    fn cells_move_whichever_vertex_they_start_from() {
+        let (from, to) = (Pos::new(0.0, 0.0), Pos::new(0.5, 0.0));
+        for offset in 0..4 {
+            let cell = lerp_cell(&square(from, offset), square(to, 0), 0.5);
+            assert_eq!(cell, square(Pos::new(0.25, 0.0), 0));
         }
-        _ => {}
+        assert_eq!(
+            lerp_cell(&square(from, 1), square(to, 2), 0.0),
+            square(from, 2)
+        );
+        assert_eq!(
+            lerp_cell(&square(from, 1), square(to, 2), 1.0),
+            square(to, 2)
+        );
+    }
+
+    #[test]
+    fn cells_that_change_shape_jump() {
+        let prev = square(Pos::new(0.0, 0.0), 0);
+        let cell = vec![Pos::new(0.0, 0.0), Pos::new(1.0, 0.0), Pos::new(0.0, 1.0)];
+        assert_eq!(lerp_cell(&prev, cell.clone(), 0.5), cell);
+        assert_eq!(lerp_cell(&prev, Vec::new(), 0.5), Vec::new());
     }
 }
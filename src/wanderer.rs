@@ -0,0 +1,92 @@
+// Sites of the voronoi visualiser: agents that wander around the window,
+// either as a random walk or by repelling each other.
+
+use std::f32::consts::PI;
+
+use nannou::geom::Rect;
+use rand::Rng;
+
+use crate::grid::Grid;
+use crate::pos::Pos;
+use crate::rng::{self, SimRng};
+
+#[derive(Clone)]
+pub struct Wanderer {
+    pub pos: Pos,
+    pub angle: f32,
+    pub step_size: f32,
+    rng: SimRng,
+}
+
+impl Wanderer {
+    pub fn new(pos: Pos, step_size: f32, rng: &mut SimRng) -> Wanderer {
+        Wanderer {
+            pos,
+            angle: rng.gen_range(-PI..PI),
+            step_size,
+            rng: rng::split(rng),
+        }
+    }
+
+    // `jitter` is the most the heading can turn in one step, in radians
+    pub fn update1(&mut self, win: Rect, jitter: f32) {
+        // velocity random walk
+        // add a little noise to the angle
+        self.angle += jitter * self.rng.gen_range(-1.0..1.0);
+        // step the position
+        self.pos.x += self.angle.cos() * self.step_size;
+        self.pos.y += self.angle.sin() * self.step_size;
+        // turn around if approaching the edges
+        if self.pos.x + self.angle.cos() * self.step_size * 20.0 < win.left() {
+            self.angle = PI - self.angle;
+        }
+        if self.pos.x + self.angle.cos() * self.step_size * 20.0 > win.right() {
+            self.angle = PI - self.angle;
+        }
+        if self.pos.y + self.angle.sin() * self.step_size * 20.0 < win.bottom() {
+            self.angle = -self.angle;
+        }
+        if self.pos.y + self.angle.sin() * self.step_size * 20.0 > win.top() {
+            self.angle = -self.angle;
+        }
+    }
+
+    // `sites` holds every site, this one included; only those within `cutoff`
+    // push it
+    pub fn update2(
+        &mut self,
+        win: Rect,
+        sites: &Grid,
+        cutoff: f32,
+        site_repulsion: f32,
+        bound_repulsion: f32,
+    ) {
+        let mut next_pos = self.pos;
+
+        // repel from nearby sites with inverse square power
+        for (_, site) in sites.near(self.pos, cutoff) {
+            let dxy = site - self.pos;
+            // sites include the current agent; skip if match
+            if dxy.x.abs() < f32::EPSILON && dxy.y.abs() < f32::EPSILON {
+                continue;
+            }
+            let force = dxy.magnitude().powf(-3.0);
+            next_pos = next_pos - dxy * force * site_repulsion;
+        }
+
+        // then repel from bounds
+        let bounds = [
+            Pos::new(win.left(), self.pos.y),   // left
+            Pos::new(win.right(), self.pos.y),  // right
+            Pos::new(self.pos.x, win.top()),    // top
+            Pos::new(self.pos.x, win.bottom()), // bottom
+        ];
+        for bound in bounds.iter() {
+            let dxy = *bound - self.pos;
+            let force = dxy.magnitude().powf(-2.0);
+            next_pos = next_pos - dxy * force * bound_repulsion;
+        }
+
+        self.pos = next_pos;
+    }
+}